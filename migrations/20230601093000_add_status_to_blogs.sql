@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TYPE blog_status AS ENUM ('draft', 'published', 'unlisted', 'archived');
+
+-- Everything written before this migration is already live.
+ALTER TABLE blogs
+ADD COLUMN status blog_status NOT NULL DEFAULT 'published';
+
+ALTER TABLE blogs
+ALTER COLUMN status SET DEFAULT 'draft';
@@ -0,0 +1,7 @@
+-- Add migration script here
+ALTER TABLE blogs
+ADD COLUMN publish_at TIMESTAMP(0) WITH TIME ZONE;
+
+-- The publisher looks for drafts that are due.
+CREATE INDEX IF NOT EXISTS blogs_scheduled_idx ON blogs (publish_at)
+WHERE status = 'draft' AND publish_at IS NOT NULL;
@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS blog_revisions(
+       blog_id BIGINT NOT NULL REFERENCES blogs(id) ON DELETE CASCADE,
+       version BIGINT NOT NULL,
+       url TEXT NOT NULL,
+       title TEXT NOT NULL,
+       preview TEXT NOT NULL,
+       content TEXT NOT NULL,
+       tags TEXT[] NOT NULL,
+       edit_time TIMESTAMP(0) WITH TIME ZONE NOT NULL,
+       replaced_time TIMESTAMP(0) WITH TIME ZONE NOT NULL DEFAULT NOW(),
+       PRIMARY KEY (blog_id, version)
+);
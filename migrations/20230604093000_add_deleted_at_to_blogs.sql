@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE blogs
+ADD COLUMN deleted_at TIMESTAMP(0) WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS blogs_trash_idx ON blogs (deleted_at)
+WHERE deleted_at IS NOT NULL;
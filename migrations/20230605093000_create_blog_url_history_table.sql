@@ -0,0 +1,10 @@
+-- Add migration script here
+-- Every url a blog used to have. An old url redirects to the blog and
+-- cannot be taken by another blog.
+CREATE TABLE IF NOT EXISTS blog_url_history(
+       url TEXT PRIMARY KEY,
+       blog_id BIGINT NOT NULL REFERENCES blogs(id) ON DELETE CASCADE,
+       replaced_time TIMESTAMP(0) WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS blog_url_history_blog_id_idx ON blog_url_history (blog_id);
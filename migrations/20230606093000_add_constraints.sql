@@ -0,0 +1,53 @@
+-- Add migration script here
+-- Blogs of users that no longer exist are kept, someone has to give
+-- them to an existing user before the constraints below can be added.
+DO $$
+DECLARE
+    orphans BIGINT;
+BEGIN
+    SELECT COUNT(*) INTO orphans
+    FROM blogs
+    WHERE user_id NOT IN (SELECT id FROM users);
+
+    IF orphans > 0 THEN
+        RAISE EXCEPTION '% blogs belong to users that do not exist', orphans
+        USING HINT = 'Give them to an existing user, as in UPDATE blogs SET user_id = <id> WHERE user_id NOT IN (SELECT id FROM users), then run the migrations again.';
+    END IF;
+END $$;
+
+-- Clean up the other rows that point to nothing or are duplicated,
+-- otherwise the constraints below cannot be added.
+DELETE FROM tokens
+WHERE user_id NOT IN (SELECT id FROM users);
+
+DELETE FROM tags
+WHERE blog_id NOT IN (SELECT id FROM blogs);
+
+DELETE FROM tags a
+USING tags b
+WHERE a.ctid < b.ctid AND a.blog_id = b.blog_id AND a.name = b.name;
+
+-- Deleting a user deletes their tokens and blogs, deleting a blog
+-- deletes its tags.
+ALTER TABLE tokens
+ADD CONSTRAINT tokens_user_id_fkey
+FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
+
+ALTER TABLE blogs
+ADD CONSTRAINT blogs_user_id_fkey
+FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
+
+ALTER TABLE tags
+ADD CONSTRAINT tags_blog_id_fkey
+FOREIGN KEY (blog_id) REFERENCES blogs(id) ON DELETE CASCADE;
+
+ALTER TABLE tags
+ADD CONSTRAINT tags_pkey PRIMARY KEY (blog_id, name);
+
+CREATE INDEX IF NOT EXISTS tags_name_idx ON tags (name);
+
+CREATE UNIQUE INDEX IF NOT EXISTS tokens_token_idx ON tokens (token);
+
+CREATE INDEX IF NOT EXISTS tokens_user_id_idx ON tokens (user_id);
+
+CREATE INDEX IF NOT EXISTS blogs_user_id_idx ON blogs (user_id);
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Keyset pagination walks blogs by (create_time, id).
+CREATE INDEX IF NOT EXISTS blogs_create_time_id_idx ON blogs (create_time, id);
@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TYPE content_format AS ENUM ('html', 'markdown');
+
+-- rendered_content is the html shown to readers, rendered from
+-- content whenever a blog is written.
+ALTER TABLE blogs
+ADD COLUMN content_format content_format NOT NULL DEFAULT 'html',
+ADD COLUMN rendered_content TEXT;
+
+-- Existing blogs are html, which is shown as it is.
+UPDATE blogs SET rendered_content = content;
+
+ALTER TABLE blogs ALTER COLUMN rendered_content SET NOT NULL;
+
+ALTER TABLE blog_revisions
+ADD COLUMN content_format content_format NOT NULL DEFAULT 'html';
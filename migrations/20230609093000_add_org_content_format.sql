@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TYPE content_format ADD VALUE 'org';
@@ -0,0 +1,6 @@
+-- Add migration script here
+-- auto shows a table of contents on long posts only.
+CREATE TYPE toc_mode AS ENUM ('auto', 'show', 'hide');
+
+ALTER TABLE blogs
+ADD COLUMN toc toc_mode NOT NULL DEFAULT 'auto';
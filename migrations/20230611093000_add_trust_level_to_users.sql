@@ -0,0 +1,8 @@
+-- Add migration script here
+-- Html written by untrusted users is sanitized before it is stored,
+-- trusted users may write any html. Existing users are untrusted
+-- until marked otherwise with --trust-user.
+CREATE TYPE trust_level AS ENUM ('untrusted', 'trusted');
+
+ALTER TABLE users
+ADD COLUMN trust_level trust_level NOT NULL DEFAULT 'untrusted';
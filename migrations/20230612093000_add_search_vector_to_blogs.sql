@@ -0,0 +1,16 @@
+-- Add migration script here
+-- Text of some html without its tags, for searching.
+CREATE FUNCTION html_to_text(html TEXT) RETURNS TEXT
+LANGUAGE SQL IMMUTABLE STRICT
+AS $$ SELECT regexp_replace(html, '<[^>]*>', ' ', 'g') $$;
+
+-- Kept up to date by postgres whenever a blog is written, matches in
+-- the title rank above the preview, and the preview above the content.
+ALTER TABLE blogs
+ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', title), 'A') ||
+    setweight(to_tsvector('english', html_to_text(preview)), 'B') ||
+    setweight(to_tsvector('english', html_to_text(rendered_content)), 'C')
+) STORED;
+
+CREATE INDEX blogs_search_vector_idx ON blogs USING GIN (search_vector);
@@ -0,0 +1,6 @@
+-- Add migration script here
+-- Tags only exist as names on blogs, a description is kept by name.
+CREATE TABLE IF NOT EXISTS tag_descriptions (
+       name TEXT PRIMARY KEY,
+       description TEXT NOT NULL
+);
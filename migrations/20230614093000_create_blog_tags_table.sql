@@ -0,0 +1,66 @@
+-- Add migration script here
+-- Tags become their own rows, with a slug for their urls, and blogs
+-- are tagged through blog_tags. The old table is moved out of the way
+-- while its rows are copied.
+ALTER TABLE tags RENAME TO legacy_tags;
+ALTER TABLE legacy_tags RENAME CONSTRAINT tags_pkey TO legacy_tags_pkey;
+
+CREATE TABLE IF NOT EXISTS tags (
+       id BIGSERIAL PRIMARY KEY,
+       name TEXT NOT NULL UNIQUE,
+       slug TEXT NOT NULL UNIQUE,
+       description TEXT
+);
+
+CREATE TABLE IF NOT EXISTS blog_tags (
+       blog_id BIGINT NOT NULL REFERENCES blogs(id) ON DELETE CASCADE,
+       tag_id BIGINT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+       PRIMARY KEY (blog_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS blog_tags_tag_id_idx ON blog_tags (tag_id);
+
+-- Slugs are made as the server makes them, except that letters outside
+-- of ascii are dropped instead of transliterated. A slug already taken
+-- gets a numbered suffix, as in foo, foo-1, foo-2.
+DO $$
+DECLARE
+    tag_name TEXT;
+    base TEXT;
+    candidate TEXT;
+    n INT;
+BEGIN
+    FOR tag_name IN
+        SELECT name FROM legacy_tags
+        UNION
+        SELECT name FROM tag_descriptions
+        ORDER BY name
+    LOOP
+        base := trim(BOTH '-' FROM lower(regexp_replace(tag_name, '[^a-zA-Z0-9]+', '-', 'g')));
+        IF base = '' THEN
+            base := 'tag';
+        END IF;
+
+        candidate := base;
+        n := 1;
+        WHILE EXISTS (SELECT 1 FROM tags WHERE slug = candidate) LOOP
+            candidate := base || '-' || n;
+            n := n + 1;
+        END LOOP;
+
+        INSERT INTO tags (name, slug) VALUES (tag_name, candidate);
+    END LOOP;
+END $$;
+
+UPDATE tags
+SET description = tag_descriptions.description
+FROM tag_descriptions
+WHERE tag_descriptions.name = tags.name;
+
+INSERT INTO blog_tags (blog_id, tag_id)
+SELECT legacy_tags.blog_id, tags.id
+FROM legacy_tags
+JOIN tags ON tags.name = legacy_tags.name;
+
+DROP TABLE legacy_tags;
+DROP TABLE tag_descriptions;
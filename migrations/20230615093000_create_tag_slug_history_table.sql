@@ -0,0 +1,10 @@
+-- Add migration script here
+-- Every slug a tag used to have. An old slug redirects to the tag and
+-- cannot be taken by another tag.
+CREATE TABLE IF NOT EXISTS tag_slug_history(
+       slug TEXT PRIMARY KEY,
+       tag_id BIGINT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+       replaced_time TIMESTAMP(0) WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS tag_slug_history_tag_id_idx ON tag_slug_history (tag_id);
@@ -2,19 +2,28 @@ use axum::Router;
 use sqlx::PgPool;
 use std::sync::Arc;
 
-use crate::{config::Config, rest, web};
+use crate::{config::Config, render::sanitize::Sanitizer, rest, web, web::theme::Theme};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: PgPool,
     pub config: Arc<Config>,
+    pub theme: Arc<Theme>,
+    pub sanitizer: Arc<Sanitizer>,
 }
 
 impl AppState {
-    pub fn new(db: PgPool, config: Config) -> Self {
+    pub fn new(db: PgPool, config: Config, theme: Theme) -> Self {
+        let sanitizer = Sanitizer::new(
+            &config.sanitize_allow_tags,
+            &config.sanitize_allow_attributes,
+        );
+
         Self {
             db,
             config: Arc::new(config),
+            theme: Arc::new(theme),
+            sanitizer: Arc::new(sanitizer),
         }
     }
 }
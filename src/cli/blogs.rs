@@ -0,0 +1,33 @@
+use std::{eprintln, println};
+
+use sqlx::PgPool;
+
+use crate::{data::blogs, render::sanitize::Sanitizer};
+
+pub async fn purge_trash_older_than(days: i64, pool: PgPool) {
+    let mut conn = pool.acquire().await.unwrap();
+
+    let time = chrono::Utc::now() - chrono::Duration::days(days);
+
+    match blogs::purge_trashed_blogs_before(time, &mut conn).await {
+        Ok(count) => {
+            println!("Purged {} blog(s) trashed before {}", count, time);
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+        }
+    }
+}
+
+pub async fn rerender(pool: PgPool, sanitizer: &Sanitizer) {
+    let mut conn = pool.acquire().await.unwrap();
+
+    match blogs::rerender_all_blogs(sanitizer, &mut conn).await {
+        Ok(count) => {
+            println!("Rerendered {} blog(s)", count);
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+        }
+    }
+}
@@ -28,3 +28,23 @@ id: {}",
         },
     }
 }
+
+pub async fn set_trust_level(username: String, trust_level: users::TrustLevel, pool: PgPool) {
+    let mut conn = pool.acquire().await.unwrap();
+
+    match users::set_trust_level(username.clone(), trust_level, &mut conn).await {
+        Ok(user) => {
+            let level = match user.trust_level {
+                users::TrustLevel::Trusted => "trusted",
+                users::TrustLevel::Untrusted => "untrusted",
+            };
+            println!("User {} is now {}", user.username, level);
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            eprintln!("User {} does not exist", username);
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+        }
+    }
+}
@@ -1,4 +1,9 @@
+use std::path::PathBuf;
+
 use clap::Parser;
+use serde::Serialize;
+
+use crate::render::highlight;
 
 #[derive(Parser)]
 pub struct Config {
@@ -28,4 +33,166 @@ pub struct Config {
 
     #[arg(long)]
     pub migrate: bool,
+
+    /// Let the user with this username write any html, which is
+    /// otherwise sanitized, then exit.
+    #[arg(long)]
+    pub trust_user: Option<String>,
+
+    /// Sanitize the html written by the user with this username again,
+    /// then exit.
+    #[arg(long)]
+    pub untrust_user: Option<String>,
+
+    /// Permanently delete blogs that have been in the trash for more
+    /// than this many days, then exit.
+    #[arg(long)]
+    pub purge_trash_older_than: Option<i64>,
+
+    /// Render the content of every blog again, after the renderer has
+    /// changed, then exit.
+    #[arg(long)]
+    pub rerender: bool,
+
+    /// Number of blogs on each page of the public site.
+    #[arg(long, default_value_t = 10)]
+    pub page_size: i64,
+
+    /// Number of blogs in each feed.
+    #[arg(long, default_value_t = 20)]
+    pub feed_size: i64,
+
+    /// Only put the preview of each blog in the feeds, instead of the
+    /// full content.
+    #[arg(long)]
+    pub feed_preview_only: bool,
+
+    /// Seconds between runs of the scheduled blog publisher.
+    #[arg(long, default_value_t = 60)]
+    pub publish_interval: u64,
+
+    /// Path that crawlers should not visit, listed in robots.txt. Can
+    /// be given more than once.
+    #[arg(long, default_value = "/api/")]
+    pub robots_disallow: Vec<String>,
+
+    /// Public url of the blog, every link of the site is generated
+    /// from it.
+    #[arg(long, default_value = "https://blog.drshapeless.com", value_parser = parse_base_url)]
+    pub base_url: String,
+
+    /// Title of the blog, shown in the header and in every page title.
+    #[arg(long, default_value = "drshapeless blog")]
+    pub site_title: String,
+
+    /// Author name put in the feeds.
+    #[arg(long, default_value = "drshapeless")]
+    pub site_author: String,
+
+    /// Url where the favicons and the web manifest are served.
+    #[arg(long, default_value = "https://drshapeless.com", value_parser = parse_base_url)]
+    pub asset_url: String,
+
+    /// Url of the stylesheet used by every page.
+    #[arg(long, default_value = "https://drshapeless.com/css/default.css")]
+    pub stylesheet_url: String,
+
+    /// Link in the nav bar, written as "Label=URL". Can be given more
+    /// than once.
+    #[arg(
+        long = "nav-link",
+        value_parser = parse_nav_link,
+        default_values = [
+            "Main site=https://drshapeless.com",
+            "About me=https://drshapeless.com/about_me.html",
+            "Contact=https://drshapeless.com/contact.html",
+            "Taste=https://drshapeless.com/taste.html",
+        ],
+    )]
+    pub nav_links: Vec<NavLink>,
+
+    /// Link on the right side of the nav bar, written as "Label=URL".
+    /// Can be given more than once.
+    #[arg(
+        long = "nav-link-right",
+        value_parser = parse_nav_link,
+        default_values = ["GitHub=https://github.com/drshapeless"],
+    )]
+    pub nav_links_right: Vec<NavLink>,
+
+    /// Text shown at the bottom of every page.
+    #[arg(long)]
+    pub footer: Option<String>,
+
+    /// Posts with at least this many characters of content get a table
+    /// of contents, unless it is turned off for the post.
+    #[arg(long, default_value_t = 5000)]
+    pub toc_min_length: usize,
+
+    /// Colour theme of the highlighted code blocks, served at
+    /// /highlight.css.
+    #[arg(long, default_value = "InspiredGitHub", value_parser = parse_highlight_theme)]
+    pub highlight_theme: String,
+
+    /// Tag allowed in the html of untrusted users, on top of the
+    /// default allow-list. Can be given more than once.
+    #[arg(long = "sanitize-allow-tag")]
+    pub sanitize_allow_tags: Vec<String>,
+
+    /// Attribute allowed in the html of untrusted users, written as
+    /// "tag=attribute", or "*=attribute" for every tag. Can be given
+    /// more than once.
+    #[arg(long = "sanitize-allow-attribute", value_parser = parse_tag_attribute)]
+    pub sanitize_allow_attributes: Vec<(String, String)>,
+
+    /// Directory of templates overriding the built-in ones, matched by
+    /// file name.
+    #[arg(long)]
+    pub theme_dir: Option<PathBuf>,
+
+    /// Development mode, reload the theme whenever a template in
+    /// --theme-dir changes.
+    #[arg(long)]
+    pub dev: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct NavLink {
+    pub label: String,
+    pub url: String,
+}
+
+fn parse_nav_link(s: &str) -> Result<NavLink, String> {
+    let (label, url) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected \"Label=URL\", got \"{}\"", s))?;
+
+    Ok(NavLink {
+        label: label.to_string(),
+        url: url.to_string(),
+    })
+}
+
+fn parse_tag_attribute(s: &str) -> Result<(String, String), String> {
+    let (tag, attribute) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected \"tag=attribute\", got \"{}\"", s))?;
+
+    Ok((tag.to_ascii_lowercase(), attribute.to_ascii_lowercase()))
+}
+
+fn parse_highlight_theme(s: &str) -> Result<String, String> {
+    let mut themes = highlight::theme_names();
+    if themes.contains(&s) {
+        return Ok(s.to_string());
+    }
+
+    themes.sort_unstable();
+    Err(format!("expected one of {}", themes.join(", ")))
+}
+
+// Links are built as "{base_url}/path", so a trailing slash would
+// double up.
+fn parse_base_url(s: &str) -> Result<String, String> {
+    Ok(s.trim_end_matches('/').to_string())
 }
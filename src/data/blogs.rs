@@ -1,7 +1,56 @@
+use super::tags::Tag;
+use super::users::TrustLevel;
 use super::Result;
+use crate::render::{
+    self,
+    sanitize::{SanitizeReport, Sanitizer},
+};
 use chrono::{DateTime, Utc};
-use serde::Serialize;
-use sqlx::PgConnection;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[sqlx(type_name = "blog_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum BlogStatus {
+    // Only visible to the owner.
+    Draft,
+    // Listed on the site.
+    Published,
+    // Reachable by url, but not listed anywhere.
+    Unlisted,
+    // Only visible to the owner, kept for the record.
+    Archived,
+}
+
+impl BlogStatus {
+    // Whether someone other than the owner can read the blog.
+    pub fn is_readable(&self) -> bool {
+        matches!(self, Self::Published | Self::Unlisted)
+    }
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[sqlx(type_name = "content_format", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ContentFormat {
+    #[default]
+    Html,
+    Markdown,
+    Org,
+}
+
+// Whether a post shows a table of contents.
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[sqlx(type_name = "toc_mode", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TocMode {
+    // Only when the post is long enough.
+    #[default]
+    Auto,
+    Show,
+    Hide,
+}
 
 #[derive(sqlx::FromRow, Serialize)]
 pub struct Blog {
@@ -11,26 +60,63 @@ pub struct Blog {
     pub title: String,
     pub preview: String,
     pub content: String,
+    pub content_format: ContentFormat,
+    // Written along with content, never set by hand.
+    pub rendered_content: String,
+    pub toc: TocMode,
     pub create_time: DateTime<Utc>,
     pub edit_time: DateTime<Utc>,
     version: i64,
+    pub status: BlogStatus,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(sqlx::FromRow, Serialize)]
 pub struct FullBlog {
-    id: i64,
+    pub id: i64,
     pub user_id: i64,
     pub url: String,
     pub title: String,
     pub preview: String,
     pub content: String,
+    pub content_format: ContentFormat,
+    pub rendered_content: String,
+    pub toc: TocMode,
     pub create_time: DateTime<Utc>,
     pub edit_time: DateTime<Utc>,
+    pub status: BlogStatus,
+    pub publish_at: Option<DateTime<Utc>>,
     pub tags: Vec<String>,
+    // Slugs of the tags, in the same order.
+    pub tag_slugs: Vec<String>,
+}
+
+impl Blog {
+    pub fn version(&self) -> i64 {
+        self.version
+    }
 }
 
 impl FullBlog {
-    pub fn from_blog_and_tags(blog: Blog, tags: Vec<String>) -> Self {
+    // Drafts, archived and scheduled blogs are only readable by their
+    // owner.
+    pub fn is_readable_by(&self, user_id: i64) -> bool {
+        if self.user_id == user_id {
+            return true;
+        }
+
+        let is_due = match self.publish_at {
+            Some(publish_at) => publish_at <= Utc::now(),
+            None => true,
+        };
+
+        self.status.is_readable() && is_due
+    }
+
+    pub fn from_blog_and_tags(blog: Blog, tags: Vec<Tag>) -> Self {
+        let (tags, tag_slugs) = tags.into_iter().map(|tag| (tag.name, tag.slug)).unzip();
+
         FullBlog {
             id: blog.id,
             user_id: blog.user_id,
@@ -38,9 +124,15 @@ impl FullBlog {
             title: blog.title,
             preview: blog.preview,
             content: blog.content,
+            content_format: blog.content_format,
+            rendered_content: blog.rendered_content,
+            toc: blog.toc,
             create_time: blog.create_time,
             edit_time: blog.edit_time,
+            status: blog.status,
+            publish_at: blog.publish_at,
             tags,
+            tag_slugs,
         }
     }
 }
@@ -51,16 +143,39 @@ pub struct NewBlog {
     title: String,
     preview: String,
     content: String,
+    content_format: ContentFormat,
+    toc: TocMode,
+    // Now when not given.
+    create_time: Option<DateTime<Utc>>,
+    status: BlogStatus,
+    publish_at: Option<DateTime<Utc>>,
 }
 
 impl NewBlog {
-    pub fn new(user_id: i64, url: String, title: String, preview: String, content: String) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        user_id: i64,
+        url: String,
+        title: String,
+        preview: String,
+        content: String,
+        content_format: ContentFormat,
+        toc: TocMode,
+        create_time: Option<DateTime<Utc>>,
+        status: BlogStatus,
+        publish_at: Option<DateTime<Utc>>,
+    ) -> Self {
         NewBlog {
             user_id,
             url,
             title,
             preview,
             content,
+            content_format,
+            toc,
+            create_time,
+            status,
+            publish_at,
         }
     }
 }
@@ -71,19 +186,24 @@ pub struct ForceNewBlog {
     title: String,
     preview: String,
     content: String,
+    content_format: ContentFormat,
     create_time: DateTime<Utc>,
     edit_time: DateTime<Utc>,
+    status: BlogStatus,
 }
 
 impl ForceNewBlog {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_id: i64,
         url: String,
         title: String,
         preview: String,
         content: String,
+        content_format: ContentFormat,
         create_time: DateTime<Utc>,
         edit_time: DateTime<Utc>,
+        status: BlogStatus,
     ) -> Self {
         ForceNewBlog {
             user_id,
@@ -91,8 +211,10 @@ impl ForceNewBlog {
             title,
             preview,
             content,
+            content_format,
             create_time,
             edit_time,
+            status,
         }
     }
 }
@@ -106,51 +228,112 @@ pub struct SimpleBlog {
     pub preview: String,
     pub create_time: DateTime<Utc>,
     pub edit_time: DateTime<Utc>,
+    pub status: BlogStatus,
+    pub publish_at: Option<DateTime<Utc>>,
     pub tags: Vec<String>,
+    // Slugs of the tags, in the same order.
+    pub tag_slugs: Vec<String>,
 }
 
-pub async fn create_blog(new_blog: NewBlog, conn: &mut PgConnection) -> Result<Blog> {
+pub async fn create_blog(
+    new_blog: NewBlog,
+    sanitizer: Option<&Sanitizer>,
+    conn: &mut PgConnection,
+) -> Result<(Blog, SanitizeReport)> {
     let q = "
-INSERT INTO blogs (user_id, url, title, preview, content)
-VALUES ($1, $2, $3, $4, $5)
+INSERT INTO blogs (user_id, url, title, preview, content, content_format, rendered_content, toc, create_time, status, publish_at)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, COALESCE($9, NOW()), $10, $11)
 RETURNING *";
 
+    let (rendered_content, stripped_content) =
+        render::render_content(&new_blog.content, new_blog.content_format, sanitizer);
+
     let blog = sqlx::query_as::<_, Blog>(q)
         .bind(new_blog.user_id)
         .bind(new_blog.url)
         .bind(new_blog.title)
         .bind(new_blog.preview)
         .bind(new_blog.content)
+        .bind(new_blog.content_format)
+        .bind(rendered_content)
+        .bind(new_blog.toc)
+        .bind(new_blog.create_time)
+        .bind(new_blog.status)
+        .bind(new_blog.publish_at)
         .fetch_one(conn)
         .await?;
 
-    Ok(blog)
+    let report = SanitizeReport {
+        content: stripped_content,
+    };
+
+    Ok((blog, report))
 }
 
-pub async fn update_blog(updated_blog: Blog, conn: &mut PgConnection) -> Result<Blog> {
+pub async fn update_blog(
+    updated_blog: Blog,
+    sanitizer: Option<&Sanitizer>,
+    conn: &mut PgConnection,
+) -> Result<(Blog, SanitizeReport)> {
+    // The previous version is kept in blog_revisions within the same
+    // statement, so there is no way to update without a revision. The
+    // same goes for the old url, which is kept in blog_url_history.
     let q = "
+WITH revision AS (
+    INSERT INTO blog_revisions (blog_id, version, url, title, preview, content, content_format, tags, edit_time)
+    SELECT id, version, url, title, preview, content, content_format,
+           ARRAY(SELECT tags.name FROM blog_tags JOIN tags ON tags.id = blog_tags.tag_id WHERE blog_tags.blog_id = blogs.id ORDER BY tags.name),
+           edit_time
+    FROM blogs
+    WHERE id = $10 AND version = $11
+), old_url AS (
+    INSERT INTO blog_url_history (url, blog_id)
+    SELECT url, id
+    FROM blogs
+    WHERE id = $10 AND version = $11 AND url <> $1
+    ON CONFLICT (url) DO NOTHING
+), reclaimed_url AS (
+    DELETE FROM blog_url_history
+    WHERE url = $1 AND blog_id = $10
+      AND EXISTS (SELECT 1 FROM blogs WHERE id = $10 AND version = $11)
+)
 UPDATE blogs
-SET url = $1, title = $2, preview = $3, content = $4, edit_time = NOW(), version = version + 1
-WHERE id = $5 AND version = $6
+SET url = $1, title = $2, preview = $3, content = $4, content_format = $5, rendered_content = $6, toc = $7, status = $8, publish_at = $9, edit_time = NOW(), version = version + 1
+WHERE id = $10 AND version = $11
 RETURNING *";
 
+    let (rendered_content, stripped_content) = render::render_content(
+        &updated_blog.content,
+        updated_blog.content_format,
+        sanitizer,
+    );
+
     let blog = sqlx::query_as::<_, Blog>(q)
         .bind(updated_blog.url)
         .bind(updated_blog.title)
         .bind(updated_blog.preview)
         .bind(updated_blog.content)
+        .bind(updated_blog.content_format)
+        .bind(rendered_content)
+        .bind(updated_blog.toc)
+        .bind(updated_blog.status)
+        .bind(updated_blog.publish_at)
         .bind(updated_blog.id)
         .bind(updated_blog.version)
         .fetch_one(conn)
         .await?;
 
-    Ok(blog)
+    let report = SanitizeReport {
+        content: stripped_content,
+    };
+
+    Ok((blog, report))
 }
 
 pub async fn get_blog(id: i64, conn: &mut PgConnection) -> Result<Blog> {
     let q = "
 SELECT * FROM blogs
-WHERE id = $1";
+WHERE id = $1 AND deleted_at IS NULL";
 
     let blog = sqlx::query_as::<_, Blog>(q)
         .bind(id)
@@ -160,16 +343,26 @@ WHERE id = $1";
     Ok(blog)
 }
 
+// The columns of a SimpleBlog, for blogs joined to their tags through
+// blog_tags and grouped by blogs.id. A FullBlog also has
+// CONTENT_COLUMNS.
+pub(super) const BLOG_COLUMNS: &str = "blogs.id, user_id, url, title, preview, create_time, edit_time, status, publish_at, COALESCE(ARRAY_AGG(tags.name ORDER BY tags.name) FILTER (WHERE tags.name IS NOT NULL), '{}') as tags, COALESCE(ARRAY_AGG(tags.slug ORDER BY tags.name) FILTER (WHERE tags.slug IS NOT NULL), '{}') as tag_slugs";
+
+pub(super) const CONTENT_COLUMNS: &str = "content, content_format, rendered_content, toc";
+
 pub async fn get_full_blog(id: i64, conn: &mut PgConnection) -> Result<FullBlog> {
-    let q = "
-SELECT id, user_id, url, title, preview, content, create_time, edit_time, ARRAY_AGG(tags.name) as tags
+    let q = format!(
+        "
+SELECT {BLOG_COLUMNS}, {CONTENT_COLUMNS}
 FROM blogs
-JOIN tags ON blogs.id = tags.blog_id
-WHERE blogs.id = $1
+LEFT JOIN blog_tags ON blogs.id = blog_tags.blog_id
+LEFT JOIN tags ON tags.id = blog_tags.tag_id
+WHERE blogs.id = $1 AND blogs.deleted_at IS NULL
 GROUP BY blogs.id
-";
+"
+    );
 
-    let blog = sqlx::query_as::<_, FullBlog>(q)
+    let blog = sqlx::query_as::<_, FullBlog>(&q)
         .bind(id)
         .fetch_one(conn)
         .await?;
@@ -177,16 +370,23 @@ GROUP BY blogs.id
     Ok(blog)
 }
 
+// Used by the public site, only readable blogs are returned.
 pub async fn get_full_blog_by_url(url: String, conn: &mut PgConnection) -> Result<FullBlog> {
-    let q = "
-SELECT id, user_id, url, title, preview, content, create_time, edit_time, ARRAY_AGG(tags.name) as tags
+    let q = format!(
+        "
+SELECT {BLOG_COLUMNS}, {CONTENT_COLUMNS}
 FROM blogs
-JOIN tags ON blogs.id = tags.blog_id
+LEFT JOIN blog_tags ON blogs.id = blog_tags.blog_id
+LEFT JOIN tags ON tags.id = blog_tags.tag_id
 WHERE blogs.url = $1
+  AND blogs.deleted_at IS NULL
+  AND blogs.status IN ('published', 'unlisted')
+  AND (blogs.publish_at IS NULL OR blogs.publish_at <= NOW())
 GROUP BY blogs.id
-";
+"
+    );
 
-    let blog = sqlx::query_as::<_, FullBlog>(q)
+    let blog = sqlx::query_as::<_, FullBlog>(&q)
         .bind(url)
         .fetch_one(conn)
         .await?;
@@ -194,15 +394,307 @@ GROUP BY blogs.id
     Ok(blog)
 }
 
-pub async fn get_simple_blog(id: i64, conn: &mut PgConnection) -> Result<SimpleBlog> {
-    let q = "
-SELECT id, user_id, url, title, preview, create_time, edit_time, ARRAY_AGG(tags.name) as tags
+// Used by the public site, only published blogs are returned, newest
+// first. Without a limit every blog after the offset is returned.
+pub async fn get_all_simple_blogs(
+    limit: Option<i64>,
+    offset: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<SimpleBlog>> {
+    let q = format!(
+        "
+SELECT {BLOG_COLUMNS}
+FROM blogs
+LEFT JOIN blog_tags ON blogs.id = blog_tags.blog_id
+LEFT JOIN tags ON tags.id = blog_tags.tag_id
+WHERE blogs.status = 'published'
+  AND (blogs.publish_at IS NULL OR blogs.publish_at <= NOW())
+  AND blogs.deleted_at IS NULL
+GROUP BY blogs.id
+ORDER BY create_time DESC, blogs.id DESC
+LIMIT $1 OFFSET $2"
+    );
+
+    let blogs = sqlx::query_as::<_, SimpleBlog>(&q)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(conn)
+        .await?;
+
+    Ok(blogs)
+}
+
+// Same as get_all_simple_blogs, with the content.
+pub async fn get_all_full_blogs(
+    limit: Option<i64>,
+    offset: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<FullBlog>> {
+    let q = format!(
+        "
+SELECT {BLOG_COLUMNS}, {CONTENT_COLUMNS}
+FROM blogs
+LEFT JOIN blog_tags ON blogs.id = blog_tags.blog_id
+LEFT JOIN tags ON tags.id = blog_tags.tag_id
+WHERE blogs.status = 'published'
+  AND (blogs.publish_at IS NULL OR blogs.publish_at <= NOW())
+  AND blogs.deleted_at IS NULL
+GROUP BY blogs.id
+ORDER BY create_time DESC, blogs.id DESC
+LIMIT $1 OFFSET $2"
+    );
+
+    let blogs = sqlx::query_as::<_, FullBlog>(&q)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(conn)
+        .await?;
+
+    Ok(blogs)
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+// Filters for listing blogs, every field narrows the result down.
+#[derive(Default)]
+pub struct BlogFilter {
+    // Blogs owned by the viewer are listed whatever their status,
+    // everyone else only sees published blogs.
+    pub viewer_id: Option<i64>,
+    pub user_id: Option<i64>,
+    pub tags: Vec<String>,
+    // Match blogs having all the tags instead of any of them.
+    pub match_all_tags: bool,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub edited_after: Option<DateTime<Utc>>,
+    pub edited_before: Option<DateTime<Utc>>,
+}
+
+// Position of the last blog of a page, the next page starts right
+// after it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BlogCursor {
+    pub create_time: DateTime<Utc>,
+    pub id: i64,
+}
+
+pub(super) fn push_blog_filter(query_builder: &mut QueryBuilder<Postgres>, filter: &BlogFilter) {
+    query_builder.push(" WHERE blogs.deleted_at IS NULL");
+
+    query_builder.push(
+        " AND ((blogs.status = 'published' AND (blogs.publish_at IS NULL OR blogs.publish_at <= NOW()))",
+    );
+    if let Some(viewer_id) = filter.viewer_id {
+        query_builder.push(" OR blogs.user_id = ");
+        query_builder.push_bind(viewer_id);
+    }
+    query_builder.push(")");
+
+    if let Some(user_id) = filter.user_id {
+        query_builder.push(" AND blogs.user_id = ");
+        query_builder.push_bind(user_id);
+    }
+
+    if !filter.tags.is_empty() {
+        // A tag given twice is matched once, it must be counted once.
+        let mut tags = filter.tags.clone();
+        tags.sort();
+        tags.dedup();
+        let tag_count = tags.len() as i64;
+
+        query_builder.push(
+            " AND blogs.id IN (SELECT blog_id FROM blog_tags JOIN tags ON tags.id = blog_tags.tag_id WHERE tags.name = ANY(",
+        );
+        query_builder.push_bind(tags);
+        query_builder.push(")");
+        if filter.match_all_tags {
+            query_builder.push(" GROUP BY blog_id HAVING COUNT(DISTINCT tags.id) = ");
+            query_builder.push_bind(tag_count);
+        }
+        query_builder.push(")");
+    }
+
+    if let Some(time) = filter.created_after {
+        query_builder.push(" AND blogs.create_time >= ");
+        query_builder.push_bind(time);
+    }
+
+    if let Some(time) = filter.created_before {
+        query_builder.push(" AND blogs.create_time < ");
+        query_builder.push_bind(time);
+    }
+
+    if let Some(time) = filter.edited_after {
+        query_builder.push(" AND blogs.edit_time >= ");
+        query_builder.push_bind(time);
+    }
+
+    if let Some(time) = filter.edited_before {
+        query_builder.push(" AND blogs.edit_time < ");
+        query_builder.push_bind(time);
+    }
+}
+
+// Keyset pagination over (create_time, id), a page starts right after
+// the cursor.
+pub async fn get_simple_blogs_page(
+    filter: &BlogFilter,
+    order: SortOrder,
+    cursor: Option<BlogCursor>,
+    limit: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<SimpleBlog>> {
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+        "
+SELECT {BLOG_COLUMNS}
 FROM blogs
-JOIN tags ON blogs.id = tags.blog_id
-WHERE blogs.id = $1
-GROUP BY blogs.id";
+LEFT JOIN blog_tags ON blogs.id = blog_tags.blog_id
+LEFT JOIN tags ON tags.id = blog_tags.tag_id"
+    ));
+
+    push_blog_filter(&mut query_builder, filter);
+
+    let (comparison, direction) = match order {
+        SortOrder::Asc => (">", "ASC"),
+        SortOrder::Desc => ("<", "DESC"),
+    };
+
+    if let Some(cursor) = cursor {
+        query_builder.push(format!(
+            " AND (blogs.create_time, blogs.id) {} (",
+            comparison
+        ));
+        query_builder.push_bind(cursor.create_time);
+        query_builder.push(", ");
+        query_builder.push_bind(cursor.id);
+        query_builder.push(")");
+    }
 
-    let blog = sqlx::query_as::<_, SimpleBlog>(q)
+    query_builder.push(format!(
+        " GROUP BY blogs.id ORDER BY blogs.create_time {0}, blogs.id {0} LIMIT ",
+        direction
+    ));
+    query_builder.push_bind(limit);
+
+    let blogs = query_builder
+        .build_query_as::<SimpleBlog>()
+        .fetch_all(conn)
+        .await?;
+
+    Ok(blogs)
+}
+
+pub async fn count_simple_blogs(filter: &BlogFilter, conn: &mut PgConnection) -> Result<i64> {
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM blogs");
+
+    push_blog_filter(&mut query_builder, filter);
+
+    let (count,): (i64,) = query_builder.build_query_as().fetch_one(conn).await?;
+
+    Ok(count)
+}
+
+// A blog matching a search, with the best matching parts of its
+// content.
+#[derive(sqlx::FromRow, Serialize)]
+pub struct SearchResult {
+    pub id: i64,
+    pub user_id: i64,
+    pub url: String,
+    pub title: String,
+    pub preview: String,
+    pub create_time: DateTime<Utc>,
+    pub edit_time: DateTime<Utc>,
+    pub status: BlogStatus,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    // Slugs of the tags, in the same order.
+    pub tag_slugs: Vec<String>,
+    pub rank: f32,
+    // Plain text with the matching words wrapped in <mark>, any other
+    // html has been taken out.
+    pub snippet: String,
+}
+
+// Full text search over the title, preview and content, best matches
+// first. The query is written as in a web search engine: "quoted
+// phrases", or, and -excluded words.
+pub async fn search_blogs(
+    search: &str,
+    filter: &BlogFilter,
+    limit: i64,
+    offset: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<SearchResult>> {
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(format!(
+        "
+SELECT {BLOG_COLUMNS},
+       ts_rank(blogs.search_vector, search.query) AS rank,
+       ts_headline('english', html_to_text(blogs.rendered_content), search.query, 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=30, MinWords=10') AS snippet
+FROM blogs
+CROSS JOIN websearch_to_tsquery('english', "
+    ));
+    query_builder.push_bind(search.to_string());
+    query_builder.push(") AS search(query)\nLEFT JOIN blog_tags ON blogs.id = blog_tags.blog_id\nLEFT JOIN tags ON tags.id = blog_tags.tag_id");
+
+    push_blog_filter(&mut query_builder, filter);
+
+    query_builder.push(
+        " AND blogs.search_vector @@ search.query GROUP BY blogs.id, search.query ORDER BY rank DESC, blogs.create_time DESC, blogs.id DESC LIMIT ",
+    );
+    query_builder.push_bind(limit);
+    query_builder.push(" OFFSET ");
+    query_builder.push_bind(offset);
+
+    let results = query_builder
+        .build_query_as::<SearchResult>()
+        .fetch_all(conn)
+        .await?;
+
+    Ok(results)
+}
+
+pub async fn count_search_results(
+    search: &str,
+    filter: &BlogFilter,
+    conn: &mut PgConnection,
+) -> Result<i64> {
+    let mut query_builder: QueryBuilder<Postgres> =
+        QueryBuilder::new("SELECT COUNT(*) FROM blogs CROSS JOIN websearch_to_tsquery('english', ");
+    query_builder.push_bind(search.to_string());
+    query_builder.push(") AS search(query)");
+
+    push_blog_filter(&mut query_builder, filter);
+
+    query_builder.push(" AND blogs.search_vector @@ search.query");
+
+    let (count,): (i64,) = query_builder.build_query_as().fetch_one(conn).await?;
+
+    Ok(count)
+}
+
+pub async fn set_blog_status(
+    id: i64,
+    status: BlogStatus,
+    publish_at: Option<DateTime<Utc>>,
+    conn: &mut PgConnection,
+) -> Result<Blog> {
+    let q = "
+UPDATE blogs
+SET status = $1, publish_at = $2, version = version + 1
+WHERE id = $3 AND deleted_at IS NULL
+RETURNING *";
+
+    let blog = sqlx::query_as::<_, Blog>(q)
+        .bind(status)
+        .bind(publish_at)
         .bind(id)
         .fetch_one(conn)
         .await?;
@@ -210,35 +702,196 @@ GROUP BY blogs.id";
     Ok(blog)
 }
 
-pub async fn get_all_simple_blogs(conn: &mut PgConnection) -> Result<Vec<SimpleBlog>> {
+// Flip every scheduled draft whose publish_at has passed to
+// published. Returns the number of blogs published.
+pub async fn publish_due_blogs(conn: &mut PgConnection) -> Result<u64> {
     let q = "
-SELECT id, user_id, url, title, preview, create_time, edit_time, ARRAY_AGG(tags.name) as tags
+UPDATE blogs
+SET status = 'published', version = version + 1
+WHERE status = 'draft' AND publish_at IS NOT NULL AND publish_at <= NOW() AND deleted_at IS NULL";
+
+    let result = sqlx::query(q).execute(conn).await?;
+
+    Ok(result.rows_affected())
+}
+
+// Deleting a blog only moves it to the trash, a trashed blog is hidden
+// from every query until it is restored or purged.
+pub async fn delete_blog(id: i64, conn: &mut PgConnection) -> Result<bool> {
+    let q = "
+UPDATE blogs
+SET deleted_at = NOW()
+WHERE id = $1 AND deleted_at IS NULL";
+
+    let result = sqlx::query(q).bind(id).execute(conn).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct TrashedBlog {
+    pub id: i64,
+    pub user_id: i64,
+    pub url: String,
+    pub title: String,
+    pub create_time: DateTime<Utc>,
+    pub edit_time: DateTime<Utc>,
+    pub deleted_at: DateTime<Utc>,
+}
+
+pub async fn get_all_trashed_blogs(
+    user_id: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<TrashedBlog>> {
+    let q = "
+SELECT id, user_id, url, title, create_time, edit_time, deleted_at
 FROM blogs
-JOIN tags ON blogs.id = tags.blog_id
-GROUP BY blogs.id
-ORDER BY create_time DESC";
+WHERE user_id = $1 AND deleted_at IS NOT NULL
+ORDER BY deleted_at DESC";
 
-    let blogs = sqlx::query_as::<_, SimpleBlog>(q).fetch_all(conn).await?;
+    let blogs = sqlx::query_as::<_, TrashedBlog>(q)
+        .bind(user_id)
+        .fetch_all(conn)
+        .await?;
 
     Ok(blogs)
 }
 
-pub async fn delete_blog(id: i64, conn: &mut PgConnection) -> Result<bool> {
+pub async fn restore_blog(id: i64, user_id: i64, conn: &mut PgConnection) -> Result<bool> {
+    let q = "
+UPDATE blogs
+SET deleted_at = NULL
+WHERE id = $1 AND user_id = $2 AND deleted_at IS NOT NULL";
+
+    let result = sqlx::query(q).bind(id).bind(user_id).execute(conn).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Permanently delete a trashed blog, its tags, revisions and old urls
+// go with it.
+pub async fn purge_blog(id: i64, user_id: i64, conn: &mut PgConnection) -> Result<bool> {
     let q = "
 DELETE FROM blogs
-WHERE id = $1";
+WHERE id = $1 AND user_id = $2 AND deleted_at IS NOT NULL";
 
-    let result = sqlx::query(q).bind(id).execute(conn).await?;
+    let result = sqlx::query(q).bind(id).bind(user_id).execute(conn).await?;
 
     Ok(result.rows_affected() > 0)
 }
 
+// Permanently delete every blog of the user in the trash. Returns the
+// number of blogs purged.
+pub async fn purge_all_trashed_blogs(user_id: i64, conn: &mut PgConnection) -> Result<u64> {
+    let q = "
+DELETE FROM blogs
+WHERE user_id = $1 AND deleted_at IS NOT NULL";
+
+    let result = sqlx::query(q).bind(user_id).execute(conn).await?;
+
+    Ok(result.rows_affected())
+}
+
+// Permanently delete every blog trashed before the given time. Returns
+// the number of blogs purged.
+pub async fn purge_trashed_blogs_before(
+    time: DateTime<Utc>,
+    conn: &mut PgConnection,
+) -> Result<u64> {
+    let q = "
+DELETE FROM blogs
+WHERE deleted_at IS NOT NULL AND deleted_at < $1";
+
+    let result = sqlx::query(q).bind(time).execute(conn).await?;
+
+    Ok(result.rows_affected())
+}
+
+// Render the content of every blog again, including trashed ones,
+// without touching its version or edit time. The html of untrusted
+// users goes through the sanitizer again. Returns the number of blogs
+// that changed.
+pub async fn rerender_all_blogs(sanitizer: &Sanitizer, conn: &mut PgConnection) -> Result<u64> {
+    let q = "
+SELECT blogs.id, blogs.content, blogs.content_format, blogs.rendered_content, users.trust_level
+FROM blogs
+JOIN users ON users.id = blogs.user_id
+ORDER BY blogs.id";
+
+    let blogs: Vec<(i64, String, ContentFormat, String, TrustLevel)> =
+        sqlx::query_as(q).fetch_all(&mut *conn).await?;
+
+    let q = "
+UPDATE blogs
+SET rendered_content = $2
+WHERE id = $1";
+
+    let mut count = 0;
+    for (id, content, content_format, rendered_content, trust_level) in blogs {
+        let sanitizer = (trust_level == TrustLevel::Untrusted).then_some(sanitizer);
+
+        let (rerendered, _) = render::render_content(&content, content_format, sanitizer);
+        if rerendered == rendered_content {
+            continue;
+        }
+
+        sqlx::query(q)
+            .bind(id)
+            .bind(rerendered)
+            .execute(&mut *conn)
+            .await?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+// An url is reserved when it used to belong to another blog.
+pub async fn is_url_reserved(
+    url: &str,
+    blog_id: Option<i64>,
+    conn: &mut PgConnection,
+) -> Result<bool> {
+    let q = "
+SELECT EXISTS (
+    SELECT 1 FROM blog_url_history
+    WHERE url = $1 AND blog_id IS DISTINCT FROM $2
+)";
+
+    let (reserved,): (bool,) = sqlx::query_as(q)
+        .bind(url)
+        .bind(blog_id)
+        .fetch_one(conn)
+        .await?;
+
+    Ok(reserved)
+}
+
+// Find the current url of a readable blog by one of its old urls.
+pub async fn get_current_url_by_old_url(
+    old_url: String,
+    conn: &mut PgConnection,
+) -> Result<String> {
+    let q = "
+SELECT blogs.url
+FROM blog_url_history
+JOIN blogs ON blogs.id = blog_url_history.blog_id
+WHERE blog_url_history.url = $1
+  AND blogs.deleted_at IS NULL
+  AND blogs.status IN ('published', 'unlisted')
+  AND (blogs.publish_at IS NULL OR blogs.publish_at <= NOW())";
+
+    let (url,): (String,) = sqlx::query_as(q).bind(old_url).fetch_one(conn).await?;
+
+    Ok(url)
+}
+
 pub async fn get_user_id_with_blog_id(blog_id: i64, conn: &mut PgConnection) -> Result<i64> {
     let user_id = sqlx::query!(
         "
 SELECT user_id
 FROM blogs
-WHERE id = $1",
+WHERE id = $1 AND deleted_at IS NULL",
         blog_id
     )
     .fetch_one(conn)
@@ -247,44 +900,462 @@ WHERE id = $1",
     Ok(user_id.user_id)
 }
 
-pub async fn force_create_blog(blog: ForceNewBlog, conn: &mut PgConnection) -> Result<Blog> {
+pub async fn force_create_blog(
+    blog: ForceNewBlog,
+    sanitizer: Option<&Sanitizer>,
+    conn: &mut PgConnection,
+) -> Result<(Blog, SanitizeReport)> {
     let q = "
-INSERT INTO blogs (user_id, url, title, preview, content, create_time, edit_time)
-VALUES ($1, $2, $3, $4, $5, $6, $7)
+INSERT INTO blogs (user_id, url, title, preview, content, content_format, rendered_content, create_time, edit_time, status)
+VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
 RETURNING *";
 
+    let (rendered_content, stripped_content) =
+        render::render_content(&blog.content, blog.content_format, sanitizer);
+
     let blog = sqlx::query_as::<_, Blog>(q)
         .bind(blog.user_id)
         .bind(blog.url)
         .bind(blog.title)
         .bind(blog.preview)
         .bind(blog.content)
+        .bind(blog.content_format)
+        .bind(rendered_content)
         .bind(blog.create_time)
         .bind(blog.edit_time)
+        .bind(blog.status)
         .fetch_one(conn)
         .await?;
 
-    Ok(blog)
+    let report = SanitizeReport {
+        content: stripped_content,
+    };
+
+    Ok((blog, report))
 }
 
-pub async fn force_update_blog(updated_blog: Blog, conn: &mut PgConnection) -> Result<Blog> {
+pub async fn force_update_blog(
+    updated_blog: Blog,
+    sanitizer: Option<&Sanitizer>,
+    conn: &mut PgConnection,
+) -> Result<(Blog, SanitizeReport)> {
     let q = "
+WITH revision AS (
+    INSERT INTO blog_revisions (blog_id, version, url, title, preview, content, content_format, tags, edit_time)
+    SELECT id, version, url, title, preview, content, content_format,
+           ARRAY(SELECT tags.name FROM blog_tags JOIN tags ON tags.id = blog_tags.tag_id WHERE blog_tags.blog_id = blogs.id ORDER BY tags.name),
+           edit_time
+    FROM blogs
+    WHERE id = $10 AND version = $11
+), old_url AS (
+    INSERT INTO blog_url_history (url, blog_id)
+    SELECT url, id
+    FROM blogs
+    WHERE id = $10 AND version = $11 AND url <> $1
+    ON CONFLICT (url) DO NOTHING
+), reclaimed_url AS (
+    DELETE FROM blog_url_history
+    WHERE url = $1 AND blog_id = $10
+      AND EXISTS (SELECT 1 FROM blogs WHERE id = $10 AND version = $11)
+)
 UPDATE blogs
-SET url = $1, title = $2, preview = $3, content = $4, create_time = $5, edit_time = $6, version = version + 1
-WHERE id = $7 AND version = $8
+SET url = $1, title = $2, preview = $3, content = $4, content_format = $5, rendered_content = $6, create_time = $7, edit_time = $8, status = $9, version = version + 1
+WHERE id = $10 AND version = $11
 RETURNING *";
 
+    let (rendered_content, stripped_content) = render::render_content(
+        &updated_blog.content,
+        updated_blog.content_format,
+        sanitizer,
+    );
+
     let blog = sqlx::query_as::<_, Blog>(q)
         .bind(updated_blog.url)
         .bind(updated_blog.title)
         .bind(updated_blog.preview)
         .bind(updated_blog.content)
+        .bind(updated_blog.content_format)
+        .bind(rendered_content)
         .bind(updated_blog.create_time)
         .bind(updated_blog.edit_time)
+        .bind(updated_blog.status)
         .bind(updated_blog.id)
         .bind(updated_blog.version)
         .fetch_one(conn)
         .await?;
 
-    Ok(blog)
+    let report = SanitizeReport {
+        content: stripped_content,
+    };
+
+    Ok((blog, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::tags;
+    use sqlx::PgPool;
+    use std::{cell::Cell, sync::Once};
+
+    thread_local! {
+        static QUERY_COUNT: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // Counts the statements sqlx logs. Every test has a runtime on a
+    // thread of its own, so the count is per test.
+    struct QueryCounter;
+
+    impl log::Log for QueryCounter {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == "sqlx::query"
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                QUERY_COUNT.with(|count| count.set(count.get() + 1));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn start_counting_queries() {
+        static LOGGER: Once = Once::new();
+        LOGGER.call_once(|| {
+            log::set_logger(&QueryCounter).unwrap();
+            log::set_max_level(log::LevelFilter::Info);
+        });
+
+        QUERY_COUNT.with(|count| count.set(0));
+    }
+
+    fn counted_queries() -> usize {
+        QUERY_COUNT.with(Cell::get)
+    }
+
+    async fn create_test_user(conn: &mut PgConnection) -> i64 {
+        let (id,): (i64,) = sqlx::query_as(
+            "INSERT INTO users (username, hashed_password) VALUES ('tester', 'x') RETURNING id",
+        )
+        .fetch_one(conn)
+        .await
+        .unwrap();
+
+        id
+    }
+
+    async fn create_test_blog(
+        user_id: i64,
+        url: &str,
+        tag_names: &[String],
+        conn: &mut PgConnection,
+    ) -> Blog {
+        let new_blog = NewBlog::new(
+            user_id,
+            url.to_string(),
+            "title".to_string(),
+            "preview".to_string(),
+            "<p>content</p>".to_string(),
+            ContentFormat::Html,
+            TocMode::Auto,
+            None,
+            BlogStatus::Published,
+            None,
+        );
+
+        let (blog, _) = create_blog(new_blog, None, conn).await.unwrap();
+        tags::create_some_tags(tag_names, blog.id, conn)
+            .await
+            .unwrap();
+
+        blog
+    }
+
+    #[sqlx::test]
+    async fn create_some_tags_accepts_empty_list(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+
+        let blog = create_test_blog(user_id, "untagged", &[], &mut conn).await;
+
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM blog_tags WHERE blog_id = $1")
+            .bind(blog.id)
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[sqlx::test]
+    async fn get_full_blog_returns_untagged_blog(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        let blog = create_test_blog(user_id, "untagged", &[], &mut conn).await;
+
+        let full_blog = get_full_blog(blog.id, &mut conn).await.unwrap();
+
+        assert_eq!(full_blog.url, "untagged");
+        assert!(full_blog.tags.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn get_full_blog_by_url_returns_untagged_blog(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        create_test_blog(user_id, "untagged", &[], &mut conn).await;
+
+        let full_blog = get_full_blog_by_url("untagged".to_string(), &mut conn)
+            .await
+            .unwrap();
+
+        assert!(full_blog.tags.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn get_all_simple_blogs_lists_tagged_and_untagged_blogs(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        let tag_names = vec!["foo".to_string(), "bar".to_string()];
+        create_test_blog(user_id, "tagged", &tag_names, &mut conn).await;
+        create_test_blog(user_id, "untagged", &[], &mut conn).await;
+
+        let blogs = get_all_simple_blogs(None, 0, &mut conn).await.unwrap();
+
+        assert_eq!(blogs.len(), 2);
+        let tagged = blogs.iter().find(|b| b.url == "tagged").unwrap();
+        assert_eq!(tagged.tags, vec!["bar".to_string(), "foo".to_string()]);
+        let untagged = blogs.iter().find(|b| b.url == "untagged").unwrap();
+        assert!(untagged.tags.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn get_simple_blogs_page_lists_untagged_blogs(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        let blog = create_test_blog(user_id, "untagged", &[], &mut conn).await;
+        set_blog_status(blog.id, BlogStatus::Draft, None, &mut conn)
+            .await
+            .unwrap();
+
+        let filter = BlogFilter {
+            viewer_id: Some(user_id),
+            ..Default::default()
+        };
+        let blogs = get_simple_blogs_page(&filter, SortOrder::Desc, None, 10, &mut conn)
+            .await
+            .unwrap();
+
+        assert_eq!(blogs.len(), 1);
+        assert!(blogs[0].tags.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn get_simple_blogs_page_matches_all_of_repeated_tags(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        let tag_names = vec!["foo".to_string(), "bar".to_string()];
+        create_test_blog(user_id, "tagged", &tag_names, &mut conn).await;
+
+        let filter = BlogFilter {
+            tags: vec!["foo".to_string(), "foo".to_string(), "bar".to_string()],
+            match_all_tags: true,
+            ..Default::default()
+        };
+        let blogs = get_simple_blogs_page(&filter, SortOrder::Desc, None, 10, &mut conn)
+            .await
+            .unwrap();
+        assert_eq!(blogs.len(), 1);
+
+        let count = count_simple_blogs(&filter, &mut conn).await.unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[sqlx::test]
+    async fn removing_every_tag_keeps_the_blog_visible(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        let tag_names = vec!["foo".to_string()];
+        let blog = create_test_blog(user_id, "tagged", &tag_names, &mut conn).await;
+
+        tags::delete_all_tags_for_blog_id(blog.id, &mut conn)
+            .await
+            .unwrap();
+        tags::create_some_tags(&[], blog.id, &mut conn)
+            .await
+            .unwrap();
+
+        let full_blog = get_full_blog_by_url("tagged".to_string(), &mut conn)
+            .await
+            .unwrap();
+        assert!(full_blog.tags.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn get_simple_blogs_by_tag_name_skips_untagged_blogs(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        let tag_names = vec!["foo".to_string(), "bar".to_string()];
+        create_test_blog(user_id, "tagged", &tag_names, &mut conn).await;
+        create_test_blog(user_id, "untagged", &[], &mut conn).await;
+
+        let blogs = tags::get_simple_blogs_by_tag_name("foo".to_string(), 10, 0, &mut conn)
+            .await
+            .unwrap();
+
+        assert_eq!(blogs.len(), 1);
+        assert_eq!(blogs[0].url, "tagged");
+        assert_eq!(blogs[0].tags, vec!["bar".to_string(), "foo".to_string()]);
+        assert_eq!(
+            blogs[0].tag_slugs,
+            vec!["bar".to_string(), "foo".to_string()]
+        );
+    }
+
+    #[sqlx::test]
+    async fn get_full_blogs_by_tag_name_skips_untagged_blogs(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        let tag_names = vec!["foo".to_string(), "bar".to_string()];
+        create_test_blog(user_id, "tagged", &tag_names, &mut conn).await;
+        create_test_blog(user_id, "untagged", &[], &mut conn).await;
+
+        let blogs = tags::get_full_blogs_by_tag_name("foo".to_string(), None, 0, &mut conn)
+            .await
+            .unwrap();
+
+        assert_eq!(blogs.len(), 1);
+        assert_eq!(blogs[0].url, "tagged");
+        assert_eq!(blogs[0].tags, vec!["bar".to_string(), "foo".to_string()]);
+    }
+
+    #[sqlx::test]
+    async fn tag_listings_are_empty_with_only_untagged_blogs(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        create_test_blog(user_id, "untagged", &[], &mut conn).await;
+
+        let blogs = tags::get_simple_blogs_by_tag_name("foo".to_string(), 10, 0, &mut conn)
+            .await
+            .unwrap();
+        assert!(blogs.is_empty());
+
+        let summaries =
+            tags::get_tag_summaries(&BlogFilter::default(), tags::TagOrder::Name, &mut conn)
+                .await
+                .unwrap();
+        assert!(summaries.is_empty());
+
+        let slugs = tags::get_all_tag_slugs(&mut conn).await.unwrap();
+        assert!(slugs.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn get_tag_summaries_counts_only_tagged_blogs(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        let tag_names = vec!["foo".to_string(), "bar".to_string()];
+        create_test_blog(user_id, "tagged", &tag_names, &mut conn).await;
+        let tag_names = vec!["foo".to_string()];
+        create_test_blog(user_id, "tagged-foo", &tag_names, &mut conn).await;
+        create_test_blog(user_id, "untagged", &[], &mut conn).await;
+
+        let summaries =
+            tags::get_tag_summaries(&BlogFilter::default(), tags::TagOrder::Name, &mut conn)
+                .await
+                .unwrap();
+
+        let counts: Vec<(&str, i64)> = summaries
+            .iter()
+            .map(|summary| (summary.name.as_str(), summary.blog_count))
+            .collect();
+        assert_eq!(counts, vec![("bar", 1), ("foo", 2)]);
+
+        let slugs = tags::get_all_tag_slugs(&mut conn).await.unwrap();
+        assert_eq!(slugs, vec!["bar".to_string(), "foo".to_string()]);
+    }
+
+    #[sqlx::test]
+    async fn renamed_and_merged_tags_keep_their_old_slugs(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        let tag_names = vec!["foo".to_string(), "bar".to_string()];
+        create_test_blog(user_id, "tagged", &tag_names, &mut conn).await;
+
+        let foo = tags::get_tag_by_name("foo", &mut conn)
+            .await
+            .unwrap()
+            .unwrap();
+        tags::rename_tag(foo.id, "baz", &mut conn).await.unwrap();
+
+        let tag = tags::get_tag_by_old_slug("foo", &mut conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tag.slug, "baz");
+
+        // The old slug stays with the renamed tag.
+        let new_foo = tags::get_or_create_tag("foo", &mut conn).await.unwrap();
+        assert_eq!(new_foo.slug, "foo-1");
+
+        tags::merge_tags(&["baz".to_string()], "bar", &mut conn)
+            .await
+            .unwrap();
+
+        for slug in ["foo", "baz"] {
+            let tag = tags::get_tag_by_old_slug(slug, &mut conn)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(tag.slug, "bar");
+        }
+    }
+
+    // Listings must not run a query per blog, however many blogs there
+    // are.
+    #[sqlx::test]
+    async fn blog_listings_run_a_single_query(pool: PgPool) {
+        let mut conn = pool.acquire().await.unwrap();
+        let user_id = create_test_user(&mut conn).await;
+        for i in 0..5 {
+            let tag_names = vec!["foo".to_string(), format!("bar{}", i)];
+            create_test_blog(user_id, &format!("blog-{}", i), &tag_names, &mut conn).await;
+        }
+
+        start_counting_queries();
+        let blogs = tags::get_simple_blogs_by_tag_name("foo".to_string(), 10, 0, &mut conn)
+            .await
+            .unwrap();
+        assert_eq!(counted_queries(), 1);
+        assert_eq!(blogs.len(), 5);
+        for blog in &blogs {
+            let i = blog.url.trim_start_matches("blog-");
+            assert_eq!(blog.tags, vec![format!("bar{}", i), "foo".to_string()]);
+        }
+
+        start_counting_queries();
+        let blogs = tags::get_full_blogs_by_tag_name("foo".to_string(), Some(10), 0, &mut conn)
+            .await
+            .unwrap();
+        assert_eq!(counted_queries(), 1);
+        assert_eq!(blogs.len(), 5);
+
+        start_counting_queries();
+        let blogs = get_all_simple_blogs(None, 0, &mut conn).await.unwrap();
+        assert_eq!(counted_queries(), 1);
+        assert_eq!(blogs.len(), 5);
+
+        start_counting_queries();
+        let blogs = get_all_full_blogs(None, 0, &mut conn).await.unwrap();
+        assert_eq!(counted_queries(), 1);
+        assert_eq!(blogs.len(), 5);
+
+        start_counting_queries();
+        let blogs =
+            get_simple_blogs_page(&BlogFilter::default(), SortOrder::Desc, None, 10, &mut conn)
+                .await
+                .unwrap();
+        assert_eq!(counted_queries(), 1);
+        assert_eq!(blogs.len(), 5);
+    }
 }
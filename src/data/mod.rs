@@ -1,4 +1,5 @@
 pub mod blogs;
+pub mod revisions;
 pub mod tags;
 pub mod tokens;
 pub mod users;
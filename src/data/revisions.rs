@@ -0,0 +1,62 @@
+use super::{blogs::ContentFormat, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgConnection;
+
+// A revision is a snapshot of a blog before it was updated. Revisions
+// are written by blogs::update_blog and blogs::force_update_blog.
+#[derive(sqlx::FromRow, Serialize)]
+pub struct Revision {
+    pub blog_id: i64,
+    pub version: i64,
+    pub url: String,
+    pub title: String,
+    pub preview: String,
+    pub content: String,
+    pub content_format: ContentFormat,
+    pub tags: Vec<String>,
+    pub edit_time: DateTime<Utc>,
+    pub replaced_time: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct SimpleRevision {
+    pub blog_id: i64,
+    pub version: i64,
+    pub url: String,
+    pub title: String,
+    pub edit_time: DateTime<Utc>,
+    pub replaced_time: DateTime<Utc>,
+}
+
+pub async fn get_all_simple_revisions(
+    blog_id: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<SimpleRevision>> {
+    let q = "
+SELECT blog_id, version, url, title, edit_time, replaced_time
+FROM blog_revisions
+WHERE blog_id = $1
+ORDER BY version DESC";
+
+    let revisions = sqlx::query_as::<_, SimpleRevision>(q)
+        .bind(blog_id)
+        .fetch_all(conn)
+        .await?;
+
+    Ok(revisions)
+}
+
+pub async fn get_revision(blog_id: i64, version: i64, conn: &mut PgConnection) -> Result<Revision> {
+    let q = "
+SELECT * FROM blog_revisions
+WHERE blog_id = $1 AND version = $2";
+
+    let revision = sqlx::query_as::<_, Revision>(q)
+        .bind(blog_id)
+        .bind(version)
+        .fetch_one(conn)
+        .await?;
+
+    Ok(revision)
+}
@@ -1,103 +1,277 @@
 use super::{
-    blogs::{self, SimpleBlog},
+    blogs::{self, BlogFilter, FullBlog, SimpleBlog, BLOG_COLUMNS, CONTENT_COLUMNS},
     Result,
 };
-use serde::Serialize;
-use sqlx::{PgConnection, Postgres};
+use crate::render;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use std::collections::HashSet;
 
+// A tag is shared by every blog having it, and is reached by its slug
+// on the site.
 #[derive(sqlx::FromRow, Serialize)]
 pub struct Tag {
-    name: String,
-    blog_id: i64,
+    pub id: i64,
+    pub name: String,
+    pub slug: String,
+    pub description: Option<String>,
 }
 
-async fn _create_tag(tag: Tag, conn: &mut PgConnection) -> Result<Tag> {
-    // It probably does not have to return a tag.
+// Names are transliterated to ascii, so that any name makes a readable
+// url.
+fn slug_for_name(name: &str) -> String {
+    let slug = render::slugify(&deunicode::deunicode(name));
+
+    if slug.is_empty() {
+        "tag".to_string()
+    } else {
+        slug
+    }
+}
+
+// A slug for the name that no other tag has or used to have, slugs
+// already taken get a numbered suffix, as in foo, foo-1, foo-2.
+async fn get_free_slug(name: &str, tag_id: Option<i64>, conn: &mut PgConnection) -> Result<String> {
+    let base = slug_for_name(name);
+
     let q = "
-INSERT INTO tags (name, blog_id)
+SELECT slug
+FROM tags
+WHERE (slug = $1 OR slug LIKE $1 || '-%')
+  AND id IS DISTINCT FROM $2
+UNION
+SELECT slug
+FROM tag_slug_history
+WHERE (slug = $1 OR slug LIKE $1 || '-%')
+  AND tag_id IS DISTINCT FROM $2";
+
+    let taken: HashSet<String> = sqlx::query_as::<_, (String,)>(q)
+        .bind(&base)
+        .bind(tag_id)
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .map(|(slug,)| slug)
+        .collect();
+
+    let mut slug = base.clone();
+    let mut n = 1;
+    while taken.contains(&slug) {
+        slug = format!("{}-{}", base, n);
+        n += 1;
+    }
+
+    Ok(slug)
+}
+
+// Another writer may take the name or the slug between looking for a
+// free slug and inserting, then nothing is inserted and the tag of
+// that name, if it is one, is taken instead.
+async fn create_tag(name: &str, conn: &mut PgConnection) -> Result<Tag> {
+    let q = "
+INSERT INTO tags (name, slug)
 VALUES ($1, $2)
+ON CONFLICT DO NOTHING
 RETURNING *";
 
-    let t = sqlx::query_as::<_, Tag>(q)
-        .bind(tag.name)
-        .bind(tag.blog_id)
-        .fetch_one(conn)
-        .await?;
+    loop {
+        let slug = get_free_slug(name, None, conn).await?;
+
+        let tag = sqlx::query_as::<_, Tag>(q)
+            .bind(name)
+            .bind(slug)
+            .fetch_optional(&mut *conn)
+            .await?;
 
-    Ok(t)
+        if let Some(tag) = tag {
+            return Ok(tag);
+        }
+        if let Some(tag) = get_tag_by_name(name, conn).await? {
+            return Ok(tag);
+        }
+    }
+}
+
+pub async fn get_or_create_tag(name: &str, conn: &mut PgConnection) -> Result<Tag> {
+    match get_tag_by_name(name, conn).await? {
+        Some(tag) => Ok(tag),
+        None => create_tag(name, conn).await,
+    }
 }
 
+// Tag the blog, creating the tags not used yet. Returns the tags,
+// ordered by name.
 pub async fn create_some_tags(
     tags: &[String],
     blog_id: i64,
     conn: &mut PgConnection,
-) -> Result<()> {
-    // Returns nothing when success
+) -> Result<Vec<Tag>> {
+    if tags.is_empty() {
+        // A blog without tags is fine, but an INSERT without VALUES
+        // is not.
+        return Ok(Vec::new());
+    }
+
+    let q = "
+SELECT *
+FROM tags
+WHERE name = ANY($1)";
+
+    let mut found = sqlx::query_as::<_, Tag>(q)
+        .bind(tags)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    for name in tags {
+        if !found.iter().any(|tag| tag.name == *name) {
+            let tag = create_tag(name, conn).await?;
+            found.push(tag);
+        }
+    }
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+
     let mut query_builder: sqlx::QueryBuilder<Postgres> =
-        sqlx::query_builder::QueryBuilder::new("INSERT INTO tags(name, blog_id)");
+        sqlx::query_builder::QueryBuilder::new("INSERT INTO blog_tags(blog_id, tag_id)");
 
-    query_builder.push_values(tags.iter(), |mut b, tag| {
-        b.push_bind(tag).push_bind(blog_id);
+    query_builder.push_values(found.iter(), |mut b, tag| {
+        b.push_bind(blog_id).push_bind(tag.id);
     });
 
     let query = query_builder.build();
 
     query.execute(conn).await?;
 
-    Ok(())
+    Ok(found)
 }
 
 pub async fn _get_tags_by_blog_id(id: i64, conn: &mut PgConnection) -> Result<Vec<Tag>> {
     let q = "
-SELECT * FROM tags
-WHERE blog_id = $1";
+SELECT tags.*
+FROM tags
+JOIN blog_tags ON tags.id = blog_tags.tag_id
+WHERE blog_tags.blog_id = $1
+ORDER BY tags.name";
 
     let tags = sqlx::query_as::<_, Tag>(q).bind(id).fetch_all(conn).await?;
 
     Ok(tags)
 }
 
-async fn get_blog_ids_by_tag_name(name: String, conn: &mut PgConnection) -> Result<Vec<i64>> {
-    let blog_ids = sqlx::query!(
-        "
-SELECT id
-FROM blogs
-JOIN tags ON blogs.id = tags.blog_id
-WHERE tags.name = $1
-ORDER BY id DESC",
-        name
-    )
-    .fetch_all(conn)
-    .await?;
+pub async fn get_tag_by_name(name: &str, conn: &mut PgConnection) -> Result<Option<Tag>> {
+    let q = "
+SELECT *
+FROM tags
+WHERE name = $1";
 
-    let mut ids: Vec<i64> = Vec::new();
+    let tag = sqlx::query_as::<_, Tag>(q)
+        .bind(name)
+        .fetch_optional(conn)
+        .await?;
 
-    for blog_id in blog_ids {
-        ids.push(blog_id.id);
-    }
+    Ok(tag)
+}
+
+pub async fn get_tag_by_slug(slug: &str, conn: &mut PgConnection) -> Result<Option<Tag>> {
+    let q = "
+SELECT *
+FROM tags
+WHERE slug = $1";
+
+    let tag = sqlx::query_as::<_, Tag>(q)
+        .bind(slug)
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(tag)
+}
+
+// Find a tag by one of its old slugs.
+pub async fn get_tag_by_old_slug(slug: &str, conn: &mut PgConnection) -> Result<Option<Tag>> {
+    let q = "
+SELECT tags.*
+FROM tag_slug_history
+JOIN tags ON tags.id = tag_slug_history.tag_id
+WHERE tag_slug_history.slug = $1";
 
-    Ok(ids)
+    let tag = sqlx::query_as::<_, Tag>(q)
+        .bind(slug)
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(tag)
 }
 
+// Published blogs having the tag, newest first, with every tag they
+// have, in a single query whatever the number of blogs.
 pub async fn get_simple_blogs_by_tag_name(
     name: String,
+    limit: i64,
+    offset: i64,
     conn: &mut PgConnection,
 ) -> Result<Vec<SimpleBlog>> {
-    let blog_ids = get_blog_ids_by_tag_name(name, conn).await?;
+    let q = format!(
+        "
+SELECT {BLOG_COLUMNS}
+FROM blogs
+LEFT JOIN blog_tags ON blogs.id = blog_tags.blog_id
+LEFT JOIN tags ON tags.id = blog_tags.tag_id
+WHERE blogs.id IN (SELECT blog_id FROM blog_tags JOIN tags ON tags.id = blog_tags.tag_id WHERE tags.name = $1)
+  AND blogs.status = 'published'
+  AND (blogs.publish_at IS NULL OR blogs.publish_at <= NOW())
+  AND blogs.deleted_at IS NULL
+GROUP BY blogs.id
+ORDER BY create_time DESC, blogs.id DESC
+LIMIT $2 OFFSET $3"
+    );
 
-    let mut blogs: Vec<SimpleBlog> = Vec::new();
+    let blogs = sqlx::query_as::<_, SimpleBlog>(&q)
+        .bind(name)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(conn)
+        .await?;
 
-    for id in blog_ids {
-        let blog = blogs::get_simple_blog(id, conn).await?;
-        blogs.push(blog);
-    }
+    Ok(blogs)
+}
+
+// Published blogs having the tag, newest first, with their content and
+// every tag they have.
+pub async fn get_full_blogs_by_tag_name(
+    name: String,
+    limit: Option<i64>,
+    offset: i64,
+    conn: &mut PgConnection,
+) -> Result<Vec<FullBlog>> {
+    let q = format!(
+        "
+SELECT {BLOG_COLUMNS}, {CONTENT_COLUMNS}
+FROM blogs
+LEFT JOIN blog_tags ON blogs.id = blog_tags.blog_id
+LEFT JOIN tags ON tags.id = blog_tags.tag_id
+WHERE blogs.id IN (SELECT blog_id FROM blog_tags JOIN tags ON tags.id = blog_tags.tag_id WHERE tags.name = $1)
+  AND blogs.status = 'published'
+  AND (blogs.publish_at IS NULL OR blogs.publish_at <= NOW())
+  AND blogs.deleted_at IS NULL
+GROUP BY blogs.id
+ORDER BY create_time DESC, blogs.id DESC
+LIMIT $2 OFFSET $3"
+    );
+
+    let blogs = sqlx::query_as::<_, FullBlog>(&q)
+        .bind(name)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(conn)
+        .await?;
 
     Ok(blogs)
 }
 
+// The tags themselves are kept, along with their descriptions.
 pub async fn delete_all_tags_for_blog_id(blog_id: i64, conn: &mut PgConnection) -> Result<bool> {
     let q = "
-DELETE FROM tags
+DELETE FROM blog_tags
 WHERE blog_id = $1";
 
     let result = sqlx::query(q).bind(blog_id).execute(conn).await?;
@@ -105,16 +279,236 @@ WHERE blog_id = $1";
     Ok(result.rows_affected() > 0)
 }
 
-pub async fn get_all_tag_names(conn: &mut PgConnection) -> Result<Vec<String>> {
-    let result = sqlx::query!("SELECT DISTINCT name FROM tags ORDER BY name ASC")
+// A tag with how much it is used, counting only the blogs allowed by
+// the filter.
+#[derive(sqlx::FromRow, Serialize)]
+pub struct TagSummary {
+    pub name: String,
+    pub slug: String,
+    pub description: Option<String>,
+    pub blog_count: i64,
+    // Create time of the newest blog having the tag.
+    pub latest_blog_time: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TagOrder {
+    #[default]
+    Name,
+    // Most used first.
+    Count,
+    // Most recently used first.
+    Recent,
+}
+
+pub async fn get_tag_summaries(
+    filter: &BlogFilter,
+    order: TagOrder,
+    conn: &mut PgConnection,
+) -> Result<Vec<TagSummary>> {
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        "
+SELECT tags.name, tags.slug, tags.description, COUNT(*) AS blog_count, MAX(blogs.create_time) AS latest_blog_time
+FROM blogs
+JOIN blog_tags ON blogs.id = blog_tags.blog_id
+JOIN tags ON tags.id = blog_tags.tag_id",
+    );
+
+    blogs::push_blog_filter(&mut query_builder, filter);
+
+    query_builder.push(" GROUP BY tags.id ORDER BY ");
+    query_builder.push(match order {
+        TagOrder::Name => "tags.name ASC",
+        TagOrder::Count => "blog_count DESC, tags.name ASC",
+        TagOrder::Recent => "latest_blog_time DESC, tags.name ASC",
+    });
+
+    let summaries = query_builder
+        .build_query_as::<TagSummary>()
         .fetch_all(conn)
         .await?;
 
-    let mut names: Vec<String> = Vec::new();
+    Ok(summaries)
+}
+
+// Slugs of the tags of published blogs, for the sitemap.
+pub async fn get_all_tag_slugs(conn: &mut PgConnection) -> Result<Vec<String>> {
+    let result = sqlx::query!(
+        "
+SELECT DISTINCT slug
+FROM tags
+JOIN blog_tags ON tags.id = blog_tags.tag_id
+JOIN blogs ON blogs.id = blog_tags.blog_id
+WHERE blogs.status = 'published'
+  AND (blogs.publish_at IS NULL OR blogs.publish_at <= NOW())
+  AND blogs.deleted_at IS NULL
+ORDER BY slug ASC"
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut slugs: Vec<String> = Vec::new();
 
     for r in result {
-        names.push(r.name);
+        slugs.push(r.slug);
     }
 
-    Ok(names)
+    Ok(slugs)
+}
+
+// Give the tag another name, its slug follows the name. The old slug
+// is kept in tag_slug_history, so that links to it still lead to the
+// tag. Returns the number of blogs having the tag.
+pub async fn rename_tag(id: i64, name: &str, conn: &mut PgConnection) -> Result<i64> {
+    let slug = get_free_slug(name, Some(id), conn).await?;
+
+    let q = "
+WITH old_slug AS (
+    INSERT INTO tag_slug_history (slug, tag_id)
+    SELECT slug, id
+    FROM tags
+    WHERE id = $1 AND slug <> $3
+    ON CONFLICT (slug) DO NOTHING
+), reclaimed_slug AS (
+    DELETE FROM tag_slug_history
+    WHERE slug = $3 AND tag_id = $1
+)
+UPDATE tags
+SET name = $2, slug = $3
+WHERE id = $1";
+
+    sqlx::query(q)
+        .bind(id)
+        .bind(name)
+        .bind(slug)
+        .execute(&mut *conn)
+        .await?;
+
+    let q = "
+SELECT COUNT(*)
+FROM blog_tags
+WHERE tag_id = $1";
+
+    let (count,): (i64,) = sqlx::query_as(q).bind(id).fetch_one(conn).await?;
+
+    Ok(count)
+}
+
+// Move every blog tagged with any of names to the tag into, a blog
+// having several of them ends up with into once. The merged tags are
+// gone afterwards. Returns the number of blogs changed.
+pub async fn merge_tags(names: &[String], into: &str, conn: &mut PgConnection) -> Result<i64> {
+    let into = get_or_create_tag(into, conn).await?;
+
+    let q = "
+WITH moved AS (
+    DELETE FROM blog_tags
+    USING tags
+    WHERE tags.id = blog_tags.tag_id AND tags.name = ANY($1) AND tags.id <> $2
+    RETURNING blog_tags.blog_id
+), merged AS (
+    INSERT INTO blog_tags (blog_id, tag_id)
+    SELECT DISTINCT blog_id, $2 FROM moved
+    ON CONFLICT (blog_id, tag_id) DO NOTHING
+)
+SELECT COUNT(DISTINCT blog_id) FROM moved";
+
+    let (count,): (i64,) = sqlx::query_as(q)
+        .bind(names)
+        .bind(into.id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    // The tag merged into keeps its own description, or takes the one
+    // of the first merged tag having one.
+    let q = "
+UPDATE tags
+SET description = (
+    SELECT description
+    FROM tags
+    WHERE name = ANY($1) AND id <> $2 AND description IS NOT NULL
+    ORDER BY array_position($1, name)
+    LIMIT 1
+)
+WHERE id = $2 AND description IS NULL";
+
+    sqlx::query(q)
+        .bind(names)
+        .bind(into.id)
+        .execute(&mut *conn)
+        .await?;
+
+    // Links to the merged tags lead to the tag merged into.
+    let q = "
+WITH merged AS (
+    SELECT id, slug
+    FROM tags
+    WHERE name = ANY($1) AND id <> $2
+), old_slugs AS (
+    UPDATE tag_slug_history
+    SET tag_id = $2
+    WHERE tag_id IN (SELECT id FROM merged)
+)
+INSERT INTO tag_slug_history (slug, tag_id)
+SELECT slug, $2 FROM merged
+ON CONFLICT (slug) DO NOTHING";
+
+    sqlx::query(q)
+        .bind(names)
+        .bind(into.id)
+        .execute(&mut *conn)
+        .await?;
+
+    let q = "
+DELETE FROM tags
+WHERE name = ANY($1) AND id <> $2";
+
+    sqlx::query(q)
+        .bind(names)
+        .bind(into.id)
+        .execute(conn)
+        .await?;
+
+    Ok(count)
+}
+
+// Take the tag off every blog, along with its description. Returns
+// the number of blogs changed.
+pub async fn delete_tag(id: i64, conn: &mut PgConnection) -> Result<i64> {
+    // The statement sees blog_tags as it was before the delete
+    // cascaded.
+    let q = "
+WITH deleted AS (
+    DELETE FROM tags
+    WHERE id = $1
+    RETURNING id
+)
+SELECT COUNT(*)
+FROM blog_tags
+WHERE tag_id IN (SELECT id FROM deleted)";
+
+    let (count,): (i64,) = sqlx::query_as(q).bind(id).fetch_one(conn).await?;
+
+    Ok(count)
+}
+
+// A description of None removes it.
+pub async fn set_tag_description(
+    id: i64,
+    description: Option<&str>,
+    conn: &mut PgConnection,
+) -> Result<()> {
+    let q = "
+UPDATE tags
+SET description = $2
+WHERE id = $1";
+
+    sqlx::query(q)
+        .bind(id)
+        .bind(description)
+        .execute(conn)
+        .await?;
+
+    Ok(())
 }
@@ -3,12 +3,24 @@ use sqlx::PgConnection;
 
 use super::Result;
 
+// Whether the html written by a user is kept as it is, or sanitized.
+#[derive(sqlx::Type, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[sqlx(type_name = "trust_level", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TrustLevel {
+    #[default]
+    Untrusted,
+    Trusted,
+}
+
 #[derive(sqlx::FromRow, Deserialize, Serialize)]
 pub struct User {
     pub id: i64,
     pub username: String,
     pub hashed_password: String,
     version: i64,
+    // Only changed from the command line, a user cannot trust itself.
+    pub trust_level: TrustLevel,
 }
 
 #[derive(Deserialize)]
@@ -82,6 +94,26 @@ RETURNING *";
     Ok(user)
 }
 
+pub async fn set_trust_level(
+    username: String,
+    trust_level: TrustLevel,
+    conn: &mut PgConnection,
+) -> Result<User> {
+    let q = "
+UPDATE users
+SET trust_level = $2, version = version + 1
+WHERE username = $1
+RETURNING *";
+
+    let user = sqlx::query_as::<_, User>(q)
+        .bind(username)
+        .bind(trust_level)
+        .fetch_one(conn)
+        .await?;
+
+    Ok(user)
+}
+
 pub async fn delete_user(id: i64, conn: &mut PgConnection) -> Result<bool> {
     let q = "
 DELETE FROM users
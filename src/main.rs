@@ -1,7 +1,10 @@
 use app::AppState;
 use clap::Parser;
-use cli::users::create_user;
+use cli::blogs::{purge_trash_older_than, rerender};
+use cli::users::{create_user, set_trust_level};
 use config::Config;
+use data::users::TrustLevel;
+use render::sanitize::Sanitizer;
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use tracing::error;
 use tracing_subscriber::{
@@ -9,12 +12,15 @@ use tracing_subscriber::{
     prelude::__tracing_subscriber_SubscriberExt,
     Registry,
 };
+use web::theme::Theme;
 
 mod app;
 mod cli;
 mod config;
 mod data;
 // mod log;
+mod publisher;
+mod render;
 mod rest;
 mod server;
 mod web;
@@ -78,7 +84,39 @@ async fn main() {
         return;
     }
 
-    let state = AppState::new(db, config);
+    if let Some(username) = config.trust_user {
+        set_trust_level(username, TrustLevel::Trusted, db).await;
+        return;
+    }
+
+    if let Some(username) = config.untrust_user {
+        set_trust_level(username, TrustLevel::Untrusted, db).await;
+        return;
+    }
+
+    if let Some(days) = config.purge_trash_older_than {
+        purge_trash_older_than(days, db).await;
+        return;
+    }
+
+    if config.rerender {
+        let sanitizer = Sanitizer::new(
+            &config.sanitize_allow_tags,
+            &config.sanitize_allow_attributes,
+        );
+        rerender(db, &sanitizer).await;
+        return;
+    }
+
+    let theme = match Theme::load(config.theme_dir.clone()) {
+        Ok(theme) => theme,
+        Err(err) => {
+            error!("{}", err);
+            return;
+        }
+    };
+
+    let state = AppState::new(db, config, theme);
 
     server::serve(state).await;
 }
@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::data::blogs;
+
+// Periodically publish the drafts whose publish_at has passed.
+//
+// The public queries already hide anything scheduled in the future,
+// so a late run only delays a post, it never leaks one.
+pub async fn run(db: PgPool, interval_secs: u64) {
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs.max(1)));
+
+    loop {
+        interval.tick().await;
+
+        let mut conn = match db.acquire().await {
+            Ok(conn) => conn,
+            Err(err) => {
+                error!("publisher cannot acquire connection: {:?}", err);
+                continue;
+            }
+        };
+
+        match blogs::publish_due_blogs(&mut conn).await {
+            Ok(0) => {}
+            Ok(count) => info!("published {} scheduled blog(s)", count),
+            Err(err) => error!("publisher failed: {:?}", err),
+        }
+    }
+}
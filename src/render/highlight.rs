@@ -0,0 +1,113 @@
+use std::sync::OnceLock;
+
+use syntect::{
+    highlighting::ThemeSet,
+    html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+use super::unescape_html;
+
+// Every highlighted token gets classes with this prefix, so that they
+// cannot clash with the classes of the site.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+const CODE_BLOCK_START: &str = "<pre><code class=\"language-";
+const CODE_BLOCK_END: &str = "</code></pre>";
+
+// Loading the syntaxes and themes takes a while, it is done once.
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme_set() -> &'static ThemeSet {
+    static THEME_SET: OnceLock<ThemeSet> = OnceLock::new();
+    THEME_SET.get_or_init(ThemeSet::load_defaults)
+}
+
+pub fn theme_names() -> Vec<&'static str> {
+    theme_set()
+        .themes
+        .keys()
+        .map(|name| name.as_str())
+        .collect()
+}
+
+// The stylesheet giving colours to the highlighted code for a theme.
+pub fn stylesheet(theme_name: &str) -> Option<String> {
+    let theme = theme_set().themes.get(theme_name)?;
+    css_for_theme_with_class_style(theme, CLASS_STYLE).ok()
+}
+
+// Highlight every <pre><code class="language-x"> block of some html,
+// which is what markdown and org produce for code blocks. Blocks in an
+// unknown language are left as they are.
+pub fn highlight_code_blocks(html: &str) -> String {
+    let mut highlighted = String::with_capacity(html.len() * 2);
+    let mut rest = html;
+
+    while let Some(start) = rest.find(CODE_BLOCK_START) {
+        highlighted.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let block = code_block(rest).and_then(|(lang, code, len)| {
+            let code = highlight(lang, &unescape_html(code))?;
+            Some((lang, code, len))
+        });
+
+        match block {
+            Some((lang, code, len)) => {
+                highlighted.push_str(&format!(
+                    "<pre class=\"hl-code\"><code class=\"language-{}\">{}</code></pre>",
+                    lang, code
+                ));
+                rest = &rest[len..];
+            }
+            None => {
+                highlighted.push_str(CODE_BLOCK_START);
+                rest = &rest[CODE_BLOCK_START.len()..];
+            }
+        }
+    }
+
+    highlighted.push_str(rest);
+    highlighted
+}
+
+// The language, the escaped code and the length of the block at the
+// start of some html.
+fn code_block(html: &str) -> Option<(&str, &str, usize)> {
+    let after_start = &html[CODE_BLOCK_START.len()..];
+
+    let lang_end = after_start.find("\">")?;
+    let lang = &after_start[..lang_end];
+    if lang.is_empty()
+        || !lang
+            .chars()
+            .all(|c| c.is_alphanumeric() || "+-#_.".contains(c))
+    {
+        return None;
+    }
+
+    let code_start = CODE_BLOCK_START.len() + lang_end + 2;
+    let code_len = html[code_start..].find(CODE_BLOCK_END)?;
+    let code = &html[code_start..code_start + code_len];
+
+    Some((lang, code, code_start + code_len + CODE_BLOCK_END.len()))
+}
+
+fn highlight(lang: &str, code: &str) -> Option<String> {
+    let syntax_set = syntax_set();
+    let syntax = syntax_set.find_syntax_by_token(lang)?;
+
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntax_set, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .ok()?;
+    }
+
+    Some(generator.finalize())
+}
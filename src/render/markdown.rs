@@ -0,0 +1,58 @@
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+
+use super::Anchors;
+
+pub fn to_html(content: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_HEADING_ATTRIBUTES;
+
+    let mut events: Vec<Event> = Parser::new_ext(content, options).collect();
+    add_heading_anchors(&mut events);
+
+    let mut rendered = String::with_capacity(content.len() * 3 / 2);
+    html::push_html(&mut rendered, events.into_iter());
+
+    rendered
+}
+
+// Give every heading without an explicit {#id} an id made from its
+// text, so that sections can be linked to.
+fn add_heading_anchors(events: &mut [Event]) {
+    let mut anchors = Anchors::default();
+
+    for event in events.iter() {
+        if let Event::Start(Tag::Heading { id: Some(id), .. }) = event {
+            anchors.reserve(id);
+        }
+    }
+
+    for i in 0..events.len() {
+        if !matches!(events[i], Event::Start(Tag::Heading { id: None, .. })) {
+            continue;
+        }
+
+        let text = heading_text(&events[i + 1..]);
+        let anchor = anchors.unique(&text);
+
+        if let Event::Start(Tag::Heading { id, .. }) = &mut events[i] {
+            *id = Some(CowStr::from(anchor));
+        }
+    }
+}
+
+fn heading_text(events: &[Event]) -> String {
+    let mut text = String::new();
+
+    for event in events {
+        match event {
+            Event::End(TagEnd::Heading(_)) => break,
+            Event::Text(s) | Event::Code(s) => text.push_str(s),
+            _ => {}
+        }
+    }
+
+    text
+}
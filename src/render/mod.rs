@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+
+use crate::data::blogs::ContentFormat;
+
+pub mod highlight;
+mod markdown;
+pub mod org;
+pub mod sanitize;
+pub mod toc;
+
+use sanitize::{Sanitizer, Stripped};
+
+// The html shown to readers for the content of a blog, and what the
+// sanitizer stripped from it. Html content is shown as it is, apart
+// from its code blocks being highlighted, when there is no sanitizer.
+pub fn render_content(
+    content: &str,
+    format: ContentFormat,
+    sanitizer: Option<&Sanitizer>,
+) -> (String, Vec<Stripped>) {
+    let html = match format {
+        ContentFormat::Html => content.to_string(),
+        ContentFormat::Markdown => markdown::to_html(content),
+        ContentFormat::Org => org::to_html(content),
+    };
+
+    // Markdown and org may carry raw html too, so the sanitizer runs
+    // on what they render. Anchors and highlighting come after, they
+    // are trusted.
+    let (html, stripped) = sanitize(html, sanitizer);
+
+    let html = toc::add_heading_ids(&html);
+    (highlight::highlight_code_blocks(&html), stripped)
+}
+
+// Html written by trusted users has no sanitizer, it is kept as it is.
+fn sanitize(html: String, sanitizer: Option<&Sanitizer>) -> (String, Vec<Stripped>) {
+    match sanitizer {
+        Some(sanitizer) => sanitizer.clean(&html),
+        None => (html, Vec::new()),
+    }
+}
+
+// Rendered html is escaped, this gives back the text as it was
+// written.
+fn unescape_html(html: &str) -> String {
+    html.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+// Lowercase words joined by dashes, used as the anchor of a heading.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+
+    slug
+}
+
+// Anchors of the headings of a document. Repeated headings get a
+// numbered suffix, as in foo, foo-1, foo-2.
+#[derive(Default)]
+struct Anchors {
+    used: HashSet<String>,
+}
+
+impl Anchors {
+    // Ids written by the author are kept as they are, generated ones
+    // must not take them.
+    fn reserve(&mut self, id: &str) {
+        self.used.insert(id.to_string());
+    }
+
+    fn unique(&mut self, text: &str) -> String {
+        let slug = slugify(text);
+        let base = if slug.is_empty() { "section" } else { &slug };
+
+        let mut anchor = base.to_string();
+        let mut n = 1;
+        while self.used.contains(&anchor) {
+            anchor = format!("{}-{}", base, n);
+            n += 1;
+        }
+
+        self.used.insert(anchor.clone());
+        anchor
+    }
+}
@@ -0,0 +1,1052 @@
+// A small Org renderer covering what the posts are written with:
+// headings, paragraphs, lists, links, src, example and quote blocks,
+// tables and footnotes. Keywords, comments and drawers are dropped.
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+use super::Anchors;
+
+// The in-buffer settings a blog can take its metadata from.
+#[derive(Default)]
+pub struct OrgKeywords {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub date: Option<DateTime<Utc>>,
+}
+
+pub fn keywords(content: &str) -> OrgKeywords {
+    let mut keywords = OrgKeywords::default();
+
+    for line in content.lines() {
+        let (key, value) = match keyword(line) {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        match key.to_ascii_uppercase().as_str() {
+            "TITLE" if !value.is_empty() => keywords.title = Some(value.to_string()),
+            // Both ":a:b:" and "a b" are accepted by Org.
+            "FILETAGS" => keywords.tags.extend(
+                value
+                    .split(|c: char| c == ':' || c.is_whitespace())
+                    .filter(|tag| !tag.is_empty())
+                    .map(|tag| tag.to_string()),
+            ),
+            "DATE" => keywords.date = parse_date(value),
+            _ => {}
+        }
+    }
+
+    keywords
+}
+
+// A line such as "#+TITLE: Hello", as ("TITLE", "Hello").
+fn keyword(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.trim_start().strip_prefix("#+")?.split_once(':')?;
+
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some((key, value.trim()))
+}
+
+// Dates are written as timestamps, "<2023-05-01 Mon 10:30>", or as
+// plain "2023-05-01".
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim_matches(|c| matches!(c, '<' | '>' | '[' | ']'));
+    let mut words = value.split_whitespace();
+
+    let date = NaiveDate::parse_from_str(words.next()?, "%Y-%m-%d").ok()?;
+    let time = words
+        .find_map(|word| NaiveTime::parse_from_str(word, "%H:%M").ok())
+        .unwrap_or_default();
+
+    Some(DateTime::<Utc>::from_utc(date.and_time(time), Utc))
+}
+
+pub fn to_html(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+
+    let mut renderer = Renderer::default();
+    let mut html = String::with_capacity(content.len() * 3 / 2);
+
+    renderer.blocks(&lines, &mut html);
+    renderer.footnotes(&mut html);
+
+    html
+}
+
+// Blocks, list items and emphasis nested deeper than this are written
+// as text, as each level of nesting takes some of the stack.
+const MAX_NESTING: usize = 32;
+
+#[derive(Default)]
+struct Renderer {
+    anchors: Anchors,
+    // How deep the blocks or emphasis being rendered are nested.
+    depth: usize,
+    // Footnote labels in the order they are first referenced, a
+    // footnote is numbered by its position here.
+    footnote_labels: Vec<String>,
+    footnote_definitions: HashMap<String, String>,
+    anonymous_footnotes: usize,
+}
+
+impl Renderer {
+    fn blocks(&mut self, lines: &[&str], out: &mut String) {
+        let mut i = 0;
+
+        while i < lines.len() {
+            let line = lines[i];
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() {
+                i += 1;
+            } else if let Some((level, text)) = heading(line) {
+                self.heading(level, text, out);
+                i += 1;
+            } else if let Some(kind) = block_begin(trimmed) {
+                i = self.block(kind, lines, i, out);
+            } else if is_comment(trimmed) || keyword(trimmed).is_some() {
+                i += 1;
+            } else if let Some(end) = drawer_end(lines, i) {
+                i = end + 1;
+            } else if let Some((label, text)) = footnote_definition(line) {
+                i = self.footnote_definition(label, text, lines, i);
+            } else if trimmed.starts_with('|') {
+                i = self.table(lines, i, out);
+            } else if is_rule(trimmed) {
+                out.push_str("<hr />\n");
+                i += 1;
+            } else if list_item(line).is_some() {
+                i = self.list(lines, i, out);
+            } else {
+                i = self.paragraph(lines, i, out);
+            }
+        }
+    }
+
+    // Blocks inside a quote, a list item and so on.
+    fn nested_blocks(&mut self, lines: &[&str], out: &mut String) {
+        if self.depth >= MAX_NESTING {
+            out.push_str(&format!("<p>{}</p>\n", escape(&lines.join("\n"))));
+            return;
+        }
+
+        self.depth += 1;
+        self.blocks(lines, out);
+        self.depth -= 1;
+    }
+
+    fn heading(&mut self, level: usize, text: &str, out: &mut String) {
+        let text = strip_heading_tags(text);
+        let text = ["TODO ", "DONE "]
+            .iter()
+            .find_map(|keyword| text.strip_prefix(keyword))
+            .unwrap_or(text);
+
+        // The title of the blog is the h1, top level headings are h2.
+        let level = (level + 1).min(6);
+        let anchor = self.anchors.unique(text);
+
+        out.push_str(&format!(
+            "<h{} id=\"{}\">{}</h{}>\n",
+            level,
+            escape(&anchor),
+            self.inline(text),
+            level
+        ));
+    }
+
+    // Returns the index of the line after the block.
+    fn block(&mut self, kind: &str, lines: &[&str], start: usize, out: &mut String) -> usize {
+        let end_marker = format!("#+end_{}", kind.to_ascii_lowercase());
+        let end = (start + 1..lines.len())
+            .find(|&j| lines[j].trim().eq_ignore_ascii_case(&end_marker))
+            .unwrap_or(lines.len());
+
+        let params = lines[start].trim()["#+begin_".len() + kind.len()..].trim();
+        let body = &lines[start + 1..end];
+
+        match kind.to_ascii_lowercase().as_str() {
+            "src" => {
+                let lang = params.split_whitespace().next();
+                let code = dedent(body).join("\n");
+
+                match lang {
+                    Some(lang) => out.push_str(&format!(
+                        "<pre><code class=\"language-{}\">{}</code></pre>\n",
+                        escape(lang),
+                        escape(&code)
+                    )),
+                    None => out.push_str(&format!("<pre><code>{}</code></pre>\n", escape(&code))),
+                }
+            }
+            "example" => {
+                let text = dedent(body).join("\n");
+                out.push_str(&format!("<pre>{}</pre>\n", escape(&text)));
+            }
+            "export" => {
+                if params.eq_ignore_ascii_case("html") {
+                    out.push_str(&body.join("\n"));
+                    out.push('\n');
+                }
+            }
+            "quote" => {
+                out.push_str("<blockquote>\n");
+                self.nested_blocks(body, out);
+                out.push_str("</blockquote>\n");
+            }
+            "center" => {
+                out.push_str("<div class=\"center\">\n");
+                self.nested_blocks(body, out);
+                out.push_str("</div>\n");
+            }
+            _ => self.nested_blocks(body, out),
+        }
+
+        end + 1
+    }
+
+    fn footnote_definition(
+        &mut self,
+        label: &str,
+        text: &str,
+        lines: &[&str],
+        start: usize,
+    ) -> usize {
+        let mut definition = text.to_string();
+
+        let mut i = start + 1;
+        while i < lines.len() && !ends_paragraph(lines[i]) {
+            definition.push('\n');
+            definition.push_str(lines[i].trim());
+            i += 1;
+        }
+
+        self.footnote_definitions
+            .insert(label.to_string(), definition);
+
+        i
+    }
+
+    fn table(&mut self, lines: &[&str], start: usize, out: &mut String) -> usize {
+        let end = (start..lines.len())
+            .find(|&j| !lines[j].trim().starts_with('|'))
+            .unwrap_or(lines.len());
+        let rows = &lines[start..end];
+
+        // Rows above the first rule are the header, if there are rows
+        // below it.
+        let header_len = rows
+            .iter()
+            .position(|row| is_table_rule(row))
+            .filter(|&n| n > 0 && rows[n..].iter().any(|row| !is_table_rule(row)))
+            .unwrap_or(0);
+
+        out.push_str("<table>\n");
+
+        if header_len > 0 {
+            out.push_str("<thead>\n");
+            for row in rows[..header_len].iter().filter(|row| !is_table_rule(row)) {
+                self.table_row(row, "th", out);
+            }
+            out.push_str("</thead>\n");
+        }
+
+        out.push_str("<tbody>\n");
+        for row in rows[header_len..].iter().filter(|row| !is_table_rule(row)) {
+            self.table_row(row, "td", out);
+        }
+        out.push_str("</tbody>\n</table>\n");
+
+        end
+    }
+
+    fn table_row(&mut self, row: &str, cell_tag: &str, out: &mut String) {
+        let row = row.trim();
+        let row = row.strip_prefix('|').unwrap_or(row);
+        let row = row.strip_suffix('|').unwrap_or(row);
+
+        out.push_str("<tr>");
+        for cell in row.split('|') {
+            out.push_str(&format!(
+                "<{}>{}</{}>",
+                cell_tag,
+                self.inline(cell.trim()),
+                cell_tag
+            ));
+        }
+        out.push_str("</tr>\n");
+    }
+
+    fn list(&mut self, lines: &[&str], start: usize, out: &mut String) -> usize {
+        let (indent, ordered, _) = list_item(lines[start]).unwrap();
+
+        // Every line belonging to the list, items of this level start
+        // at `indent`, anything indented further belongs to the item
+        // above it. Two blank lines, or an item of the other kind, end
+        // a list.
+        let continues = |line: &str| match indentation(line).cmp(&indent) {
+            Ordering::Less => false,
+            Ordering::Equal => matches!(list_item(line), Some((_, o, _)) if o == ordered),
+            Ordering::Greater => true,
+        };
+
+        let mut end = start + 1;
+        while end < lines.len() {
+            let line = lines[end];
+
+            if line.trim().is_empty() {
+                let next = lines.get(end + 1).copied().unwrap_or("");
+                if next.trim().is_empty() || !continues(next) {
+                    break;
+                }
+            } else if !continues(line) {
+                break;
+            }
+
+            end += 1;
+        }
+
+        let tag = if ordered { "ol" } else { "ul" };
+        out.push_str(&format!("<{}>\n", tag));
+
+        let mut i = start;
+        while i < end {
+            let (_, _, text) = list_item(lines[i]).unwrap();
+
+            let mut item_end = i + 1;
+            while item_end < end
+                && !(indentation(lines[item_end]) == indent && list_item(lines[item_end]).is_some())
+            {
+                item_end += 1;
+            }
+
+            self.list_item(text, &lines[i + 1..item_end], out);
+            i = item_end;
+        }
+
+        out.push_str(&format!("</{}>\n", tag));
+
+        end
+    }
+
+    fn list_item(&mut self, text: &str, rest: &[&str], out: &mut String) {
+        out.push_str("<li>");
+
+        let text = match checkbox(text) {
+            Some((checked, text)) => {
+                out.push_str(if checked {
+                    "<input type=\"checkbox\" disabled checked /> "
+                } else {
+                    "<input type=\"checkbox\" disabled /> "
+                });
+                text
+            }
+            None => text,
+        };
+
+        // The first paragraph of an item is not wrapped in <p>, so
+        // that tight lists stay tight.
+        let mut first = vec![text];
+        let mut i = 0;
+        while i < rest.len() && !ends_paragraph(rest[i]) {
+            first.push(rest[i].trim());
+            i += 1;
+        }
+        out.push_str(&self.inline(&first.join("\n")));
+
+        let rest = dedent(&rest[i..]);
+        if rest.iter().any(|line| !line.trim().is_empty()) {
+            out.push('\n');
+            self.nested_blocks(&rest, out);
+        }
+
+        out.push_str("</li>\n");
+    }
+
+    fn paragraph(&mut self, lines: &[&str], start: usize, out: &mut String) -> usize {
+        let mut end = start + 1;
+        while end < lines.len() && !ends_paragraph(lines[end]) {
+            end += 1;
+        }
+
+        let text: Vec<&str> = lines[start..end].iter().map(|line| line.trim()).collect();
+        out.push_str(&format!("<p>{}</p>\n", self.inline(&text.join("\n"))));
+
+        end
+    }
+
+    fn footnotes(&mut self, out: &mut String) {
+        if self.footnote_labels.is_empty() {
+            return;
+        }
+
+        out.push_str("<div class=\"footnotes\">\n<hr />\n<ol>\n");
+
+        // Definitions may reference other footnotes, which are
+        // appended to the list while it is rendered.
+        let mut n = 0;
+        while n < self.footnote_labels.len() {
+            let label = self.footnote_labels[n].clone();
+            let definition = self
+                .footnote_definitions
+                .get(&label)
+                .cloned()
+                .unwrap_or_default();
+            n += 1;
+
+            out.push_str(&format!(
+                "<li id=\"fn.{}\">{} <a href=\"#fnr.{}\" class=\"footnote-backref\">&#8617;</a></li>\n",
+                n,
+                self.inline(&definition),
+                n
+            ));
+        }
+
+        out.push_str("</ol>\n</div>\n");
+    }
+
+    // Text inside emphasis or a link description.
+    fn nested_inline(&mut self, text: &str) -> String {
+        if self.depth >= MAX_NESTING {
+            return escape(text);
+        }
+
+        self.depth += 1;
+        let html = self.inline(text);
+        self.depth -= 1;
+
+        html
+    }
+
+    fn inline(&mut self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let closings = Closings::new(&chars);
+        let mut out = String::with_capacity(text.len());
+
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+
+            if c == '[' {
+                if let Some((html, next)) = self.link(&chars, i, &closings) {
+                    out.push_str(&html);
+                    i = next;
+                    continue;
+                }
+                if let Some((html, next)) = self.footnote_reference(&chars, i, &closings) {
+                    out.push_str(&html);
+                    i = next;
+                    continue;
+                }
+            }
+
+            if let Some(end) = emphasis_end(&chars, i, &closings) {
+                let inner: String = chars[i + 1..end].iter().collect();
+
+                let html = match c {
+                    '=' | '~' => format!("<code>{}</code>", escape(&inner)),
+                    '*' => format!("<strong>{}</strong>", self.nested_inline(&inner)),
+                    '/' => format!("<em>{}</em>", self.nested_inline(&inner)),
+                    '_' => format!("<u>{}</u>", self.nested_inline(&inner)),
+                    _ => format!("<del>{}</del>", self.nested_inline(&inner)),
+                };
+                out.push_str(&html);
+
+                i = end + 1;
+                continue;
+            }
+
+            push_escaped(c, &mut out);
+            i += 1;
+        }
+
+        out
+    }
+
+    // [[target][description]] or [[target]].
+    fn link(
+        &mut self,
+        chars: &[char],
+        start: usize,
+        closings: &Closings,
+    ) -> Option<(String, usize)> {
+        if chars.get(start + 1) != Some(&'[') {
+            return None;
+        }
+
+        let close = next_position(&closings.links, start + 2)?;
+        let inner: String = chars[start + 2..close].iter().collect();
+
+        let (target, description) = match inner.split_once("][") {
+            Some((target, description)) => (target, Some(description)),
+            None => (inner.as_str(), None),
+        };
+        let target = target.strip_prefix("file:").unwrap_or(target);
+
+        let html = match description {
+            Some(description) => format!(
+                "<a href=\"{}\">{}</a>",
+                escape(target),
+                self.nested_inline(description)
+            ),
+            None if is_image(target) => {
+                format!("<img src=\"{}\" alt=\"\" />", escape(target))
+            }
+            None => format!("<a href=\"{}\">{}</a>", escape(target), escape(target)),
+        };
+
+        Some((html, close + 2))
+    }
+
+    // [fn:label], [fn:label:definition] or [fn::definition].
+    fn footnote_reference(
+        &mut self,
+        chars: &[char],
+        start: usize,
+        closings: &Closings,
+    ) -> Option<(String, usize)> {
+        let prefix = ['[', 'f', 'n', ':'];
+        if chars.get(start..start + 4)? != prefix {
+            return None;
+        }
+
+        // Definitions may contain brackets, the reference ends at the
+        // matching one.
+        let close = *closings.brackets.get(&start)?;
+
+        let inner: String = chars[start + 4..close].iter().collect();
+        let label = match inner.split_once(':') {
+            Some((label, definition)) => {
+                let label = if label.is_empty() {
+                    self.anonymous_footnotes += 1;
+                    format!("anonymous-{}", self.anonymous_footnotes)
+                } else {
+                    label.to_string()
+                };
+                self.footnote_definitions
+                    .insert(label.clone(), definition.trim().to_string());
+                label
+            }
+            None => inner,
+        };
+
+        if label.is_empty() {
+            return None;
+        }
+
+        let (n, first) = match self.footnote_labels.iter().position(|l| *l == label) {
+            Some(position) => (position + 1, false),
+            None => {
+                self.footnote_labels.push(label);
+                (self.footnote_labels.len(), true)
+            }
+        };
+
+        // Only the first reference is the target of the back link.
+        let id = if first {
+            format!(" id=\"fnr.{}\"", n)
+        } else {
+            String::new()
+        };
+
+        let html = format!(
+            "<sup><a{} href=\"#fn.{}\" class=\"footnote-reference\">{}</a></sup>",
+            id, n, n
+        );
+
+        Some((html, close + 1))
+    }
+}
+
+// "** Heading" as (2, "Heading").
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '*').count();
+    if level == 0 {
+        return None;
+    }
+
+    let text = line[level..].strip_prefix(' ')?;
+    Some((level, text.trim()))
+}
+
+// "Heading   :tag:other:" as "Heading".
+fn strip_heading_tags(text: &str) -> &str {
+    if let Some((before, tags)) = text.rsplit_once(char::is_whitespace) {
+        let is_tags = tags.len() > 2
+            && tags.starts_with(':')
+            && tags.ends_with(':')
+            && !tags.contains(char::is_whitespace);
+        if is_tags {
+            return before.trim_end();
+        }
+    }
+
+    text
+}
+
+// "#+BEGIN_SRC rust" as "SRC".
+fn block_begin(line: &str) -> Option<&str> {
+    if !line.get(..8)?.eq_ignore_ascii_case("#+begin_") {
+        return None;
+    }
+
+    let kind = line[8..].split_whitespace().next()?;
+    Some(kind)
+}
+
+fn is_comment(line: &str) -> bool {
+    line == "#" || line.starts_with("# ")
+}
+
+// A drawer such as :PROPERTIES: ... :END:, returns the line of :END:.
+fn drawer_end(lines: &[&str], start: usize) -> Option<usize> {
+    let name = lines[start].trim().strip_prefix(':')?.strip_suffix(':')?;
+
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return None;
+    }
+
+    (start + 1..lines.len()).find(|&j| lines[j].trim().eq_ignore_ascii_case(":end:"))
+}
+
+// "[fn:label] text" at the start of a line.
+fn footnote_definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix("[fn:")?;
+    let (label, text) = rest.split_once(']')?;
+
+    if label.is_empty() || label.contains(':') {
+        return None;
+    }
+
+    Some((label, text.trim()))
+}
+
+fn is_rule(line: &str) -> bool {
+    line.len() >= 5 && line.chars().all(|c| c == '-')
+}
+
+fn is_table_rule(row: &str) -> bool {
+    let row = row.trim();
+    row.starts_with("|-") && row.chars().all(|c| matches!(c, '|' | '-' | '+' | ':'))
+}
+
+// "  - text" as (2, false, "text"), "1. text" as (0, true, "text").
+fn list_item(line: &str) -> Option<(usize, bool, &str)> {
+    let indent = indentation(line);
+    let rest = line.trim_start();
+
+    for bullet in ["- ", "+ ", "* "] {
+        // At the start of a line, "* " is a heading.
+        if bullet == "* " && indent == 0 {
+            continue;
+        }
+        if let Some(text) = rest.strip_prefix(bullet) {
+            return Some((indent, false, text.trim()));
+        }
+        if rest == bullet.trim_end() {
+            return Some((indent, false, ""));
+        }
+    }
+
+    let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        let after = &rest[digits..];
+        if let Some(text) = after
+            .strip_prefix(". ")
+            .or_else(|| after.strip_prefix(") "))
+        {
+            return Some((indent, true, text.trim()));
+        }
+    }
+
+    None
+}
+
+fn checkbox(text: &str) -> Option<(bool, &str)> {
+    for (marker, checked) in [("[ ]", false), ("[X]", true), ("[x]", true), ("[-]", false)] {
+        if let Some(rest) = text.strip_prefix(marker) {
+            return Some((checked, rest.trim_start()));
+        }
+    }
+
+    None
+}
+
+// Whether a line cannot continue the paragraph above it.
+fn ends_paragraph(line: &str) -> bool {
+    let trimmed = line.trim();
+
+    trimmed.is_empty()
+        || heading(line).is_some()
+        || block_begin(trimmed).is_some()
+        || keyword(trimmed).is_some()
+        || footnote_definition(line).is_some()
+        || trimmed.starts_with('|')
+        || is_rule(trimmed)
+        || list_item(line).is_some()
+}
+
+// Counted in chars, whitespace outside of ascii takes more than one
+// byte.
+fn indentation(line: &str) -> usize {
+    line.chars().take_while(|c| c.is_whitespace()).count()
+}
+
+// Remove the indentation shared by every non blank line.
+fn dedent<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| indentation(line))
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| {
+            let start = line
+                .char_indices()
+                .nth(indent)
+                .map_or(line.len(), |(i, _)| i);
+            &line[start..]
+        })
+        .collect()
+}
+
+fn is_image(target: &str) -> bool {
+    let target = target.to_ascii_lowercase();
+    [".png", ".jpg", ".jpeg", ".gif", ".svg", ".webp"]
+        .iter()
+        .any(|ext| target.ends_with(ext))
+}
+
+// Where markup may close in a text. They are found in a single pass,
+// so that markup which never closes does not have the rest of the text
+// searched again for every character.
+struct Closings {
+    // Positions that can close emphasis, by marker.
+    emphasis: HashMap<char, Vec<usize>>,
+    // Positions of "]]".
+    links: Vec<usize>,
+    // The position of the bracket matching each opening one.
+    brackets: HashMap<usize, usize>,
+}
+
+impl Closings {
+    fn new(chars: &[char]) -> Self {
+        let mut closings = Closings {
+            emphasis: HashMap::new(),
+            links: Vec::new(),
+            brackets: HashMap::new(),
+        };
+        let mut open_brackets = Vec::new();
+
+        for (j, &c) in chars.iter().enumerate() {
+            match c {
+                '[' => open_brackets.push(j),
+                ']' => {
+                    if let Some(open) = open_brackets.pop() {
+                        closings.brackets.insert(open, j);
+                    }
+                    if chars.get(j + 1) == Some(&']') {
+                        closings.links.push(j);
+                    }
+                }
+                _ => {}
+            }
+
+            if j > 0 && closes_emphasis(chars, j) {
+                closings.emphasis.entry(c).or_default().push(j);
+            }
+        }
+
+        closings
+    }
+}
+
+// The first of the ordered positions at or after from.
+fn next_position(positions: &[usize], from: usize) -> Option<usize> {
+    let n = positions.partition_point(|&position| position < from);
+    positions.get(n).copied()
+}
+
+// Emphasis such as *bold* or =verbatim=, returns the index of the
+// closing marker. Markers must hug the text and stand apart from the
+// words around them, so that 2*3*4 or a/b/c are left alone.
+fn emphasis_end(chars: &[char], start: usize, closings: &Closings) -> Option<usize> {
+    let marker = chars[start];
+    if !is_emphasis_marker(marker) {
+        return None;
+    }
+
+    let before_ok = start == 0 || {
+        let before = chars[start - 1];
+        before.is_whitespace() || matches!(before, '-' | '(' | '{' | '\'' | '"')
+    };
+    let first = *chars.get(start + 1)?;
+    if !before_ok || first.is_whitespace() || first == marker {
+        return None;
+    }
+
+    next_position(closings.emphasis.get(&marker)?, start + 2)
+}
+
+fn is_emphasis_marker(c: char) -> bool {
+    matches!(c, '*' | '/' | '_' | '=' | '~' | '+')
+}
+
+// Whether the marker at j, not the first character, can close
+// emphasis.
+fn closes_emphasis(chars: &[char], j: usize) -> bool {
+    if !is_emphasis_marker(chars[j]) || chars[j - 1].is_whitespace() {
+        return false;
+    }
+
+    match chars.get(j + 1) {
+        None => true,
+        Some(&after) => {
+            after.is_whitespace()
+                || matches!(
+                    after,
+                    '-' | '.' | ',' | ':' | ';' | '!' | '?' | '\'' | '"' | ')' | '}' | '[' | '\\'
+                )
+        }
+    }
+}
+
+fn push_escaped(c: char, out: &mut String) {
+    match c {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        '\'' => out.push_str("&#39;"),
+        _ => out.push(c),
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        push_escaped(c, &mut escaped);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn headings_are_shifted_down_and_lose_their_tags() {
+        let html = to_html("* TODO Intro :draft:emacs:\n** Details");
+
+        assert_eq!(
+            html,
+            "<h2 id=\"intro\">Intro</h2>\n<h3 id=\"details\">Details</h3>\n"
+        );
+    }
+
+    #[test]
+    fn repeated_headings_get_unique_anchors() {
+        let html = to_html("* Notes\n* Notes");
+
+        assert!(html.contains("<h2 id=\"notes\">"));
+        assert!(html.contains("<h2 id=\"notes-1\">"));
+    }
+
+    #[test]
+    fn lists_nest_by_indentation() {
+        let html = to_html("- one\n  1. inner\n- two\n\n1) first\n2) second");
+
+        assert_eq!(
+            html,
+            "<ul>\n<li>one\n<ol>\n<li>inner</li>\n</ol>\n</li>\n<li>two</li>\n</ul>\n\
+             <ol>\n<li>first</li>\n<li>second</li>\n</ol>\n"
+        );
+    }
+
+    #[test]
+    fn checkboxes_are_disabled_inputs() {
+        let html = to_html("- [X] done\n- [ ] todo");
+
+        assert_eq!(
+            html,
+            "<ul>\n<li><input type=\"checkbox\" disabled checked /> done</li>\n\
+             <li><input type=\"checkbox\" disabled /> todo</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn src_blocks_keep_their_language_and_are_escaped() {
+        let html = to_html("#+BEGIN_SRC rust\n  if a < b {}\n#+END_SRC");
+
+        assert_eq!(
+            html,
+            "<pre><code class=\"language-rust\">if a &lt; b {}</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn example_blocks_are_preformatted_text() {
+        let html = to_html("#+begin_example\n*not bold*\n#+end_example");
+
+        assert_eq!(html, "<pre>*not bold*</pre>\n");
+    }
+
+    #[test]
+    fn dedent_keeps_lines_indented_with_wide_whitespace() {
+        let html = to_html("#+begin_src\n  one\n\u{3000}two\n    three\n#+end_src");
+
+        assert_eq!(html, "<pre><code> one\ntwo\n   three</code></pre>\n");
+
+        let html = to_html("- item\n\n  \u{3000}para\n");
+
+        assert!(html.contains("para"));
+    }
+
+    #[test]
+    fn tables_have_a_header_above_the_first_rule() {
+        let html = to_html("| a | b |\n|---+---|\n| 1 | *2* |");
+
+        assert_eq!(
+            html,
+            "<table>\n<thead>\n<tr><th>a</th><th>b</th></tr>\n</thead>\n\
+             <tbody>\n<tr><td>1</td><td><strong>2</strong></td></tr>\n</tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn tables_without_a_rule_have_no_header() {
+        let html = to_html("| a | b |");
+
+        assert_eq!(
+            html,
+            "<table>\n<tbody>\n<tr><td>a</td><td>b</td></tr>\n</tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn footnotes_are_numbered_by_first_reference() {
+        let html =
+            to_html("A[fn:b] and B[fn:a] and A again[fn:b].\n\n[fn:a] Second.\n[fn:b] First.");
+
+        assert!(html.contains(
+            "A<sup><a id=\"fnr.1\" href=\"#fn.1\" class=\"footnote-reference\">1</a></sup>"
+        ));
+        assert!(
+            html.contains("again<sup><a href=\"#fn.1\" class=\"footnote-reference\">1</a></sup>")
+        );
+        assert!(html.contains("<li id=\"fn.1\">First. "));
+        assert!(html.contains("<li id=\"fn.2\">Second. "));
+    }
+
+    #[test]
+    fn inline_footnotes_may_contain_brackets() {
+        let html = to_html("Text[fn::see [[https://example.com][here]]].");
+
+        assert!(html.contains("class=\"footnote-reference\">1</a></sup>.</p>"));
+        assert!(html.contains("<li id=\"fn.1\">see <a href=\"https://example.com\">here</a> "));
+    }
+
+    #[test]
+    fn links_with_and_without_descriptions() {
+        let html = to_html("[[https://example.com][an /example/]] [[https://example.com]]");
+
+        assert_eq!(
+            html,
+            "<p><a href=\"https://example.com\">an <em>example</em></a> \
+             <a href=\"https://example.com\">https://example.com</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn links_to_images_are_images() {
+        let html = to_html("[[file:images/cat.png]]");
+
+        assert_eq!(html, "<p><img src=\"images/cat.png\" alt=\"\" /></p>\n");
+    }
+
+    #[test]
+    fn emphasis_needs_markers_apart_from_words() {
+        let html = to_html("*bold* =code= 2*3*4 a/b/c");
+
+        assert_eq!(
+            html,
+            "<p><strong>bold</strong> <code>code</code> 2*3*4 a/b/c</p>\n"
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_written_as_text() {
+        let content = "#+begin_quote\n".repeat(5000);
+
+        let html = to_html(&content);
+
+        assert_eq!(html.matches("<blockquote>").count(), MAX_NESTING + 1);
+        assert!(html.contains("<p>#+begin_quote\n"));
+    }
+
+    #[test]
+    fn deep_emphasis_is_written_as_text() {
+        let content = "*/".repeat(5000) + &"/*".repeat(5000);
+
+        let html = to_html(&content);
+
+        assert!(html.matches("<strong>").count() <= MAX_NESTING);
+    }
+
+    #[test]
+    fn unclosed_markup_is_left_as_it_is() {
+        let content = "*a [[b [fn:c ".repeat(20000);
+
+        let html = to_html(&content);
+
+        assert!(!html.contains("<strong>"));
+        assert!(!html.contains("<a "));
+        assert!(!html.contains("<sup>"));
+    }
+
+    #[test]
+    fn keywords_give_title_tags_and_date() {
+        let keywords = keywords(
+            "#+TITLE: My post\n#+filetags: :emacs:org:\n#+FILETAGS: lisp\n#+DATE: <2023-05-01 Mon 10:30>\n\nText",
+        );
+
+        assert_eq!(keywords.title.as_deref(), Some("My post"));
+        assert_eq!(keywords.tags, vec!["emacs", "org", "lisp"]);
+        assert_eq!(
+            keywords.date,
+            Some(Utc.with_ymd_and_hms(2023, 5, 1, 10, 30, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn keywords_accept_plain_dates_and_ignore_empty_titles() {
+        let keywords = keywords("#+TITLE:\n#+DATE: 2023-05-01");
+
+        assert_eq!(keywords.title, None);
+        assert_eq!(
+            keywords.date,
+            Some(Utc.with_ymd_and_hms(2023, 5, 1, 0, 0, 0).unwrap())
+        );
+    }
+
+    #[test]
+    fn keywords_comments_and_drawers_are_dropped() {
+        let html = to_html("#+TITLE: x\n# a comment\n:PROPERTIES:\n:ID: 1\n:END:\nText");
+
+        assert_eq!(html, "<p>Text</p>\n");
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+// Markup written by the markdown and org renderers, on top of what
+// ammonia allows by default: heading anchors, footnotes, code block
+// languages and task list checkboxes.
+const RENDERER_GENERIC_ATTRIBUTES: [&str; 2] = ["id", "class"];
+const RENDERER_TAGS: [&str; 1] = ["input"];
+const RENDERER_TAG_ATTRIBUTES: [(&str, &str); 2] = [("input", "checked"), ("input", "disabled")];
+
+// Something the sanitizer took out of some html. An attribute of None
+// means the whole element.
+#[derive(Serialize, PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+pub struct Stripped {
+    pub tag: String,
+    pub attribute: Option<String>,
+    pub count: usize,
+}
+
+// What was stripped from a blog when it was written. The preview is
+// plain text, escaped wherever it is shown, so it is never sanitized.
+#[derive(Serialize, Default)]
+pub struct SanitizeReport {
+    pub content: Vec<Stripped>,
+}
+
+// Cleans html with an allow-list of tags and attributes. Everything
+// else is removed, along with urls of unknown schemes, such as
+// javascript:.
+pub struct Sanitizer {
+    builder: ammonia::Builder<'static>,
+}
+
+impl Sanitizer {
+    // Extra attributes are pairs of tag and attribute, a tag of "*"
+    // allows the attribute on every tag.
+    pub fn new(extra_tags: &[String], extra_attributes: &[(String, String)]) -> Self {
+        // The builder keeps references, the allow-list lives as long as
+        // the program anyway.
+        let leak = |s: &String| -> &'static str { Box::leak(s.clone().into_boxed_str()) };
+
+        let mut builder = ammonia::Builder::default();
+        builder
+            .add_generic_attributes(RENDERER_GENERIC_ATTRIBUTES)
+            .add_tags(RENDERER_TAGS)
+            // Only checkboxes are written by the renderers.
+            .add_tag_attribute_values("input", "type", ["checkbox"]);
+
+        for (tag, attribute) in RENDERER_TAG_ATTRIBUTES {
+            builder.add_tag_attributes(tag, [attribute]);
+        }
+
+        // Script and style are removed along with their content unless
+        // they are allowed.
+        builder
+            .rm_clean_content_tags(extra_tags)
+            .rm_clean_content_tags(extra_attributes.iter().map(|(tag, _)| tag))
+            .add_tags(extra_tags.iter().map(leak));
+
+        for (tag, attribute) in extra_attributes {
+            // Ammonia writes rel on links by itself, unless it is
+            // allowed.
+            if attribute == "rel" {
+                builder.link_rel(None);
+            }
+
+            if tag == "*" {
+                builder.add_generic_attributes([leak(attribute)]);
+            } else {
+                builder.add_tag_attributes(leak(tag), [leak(attribute)]);
+            }
+        }
+
+        Sanitizer { builder }
+    }
+
+    // The cleaned html, and what was taken out of it.
+    pub fn clean(&self, html: &str) -> (String, Vec<Stripped>) {
+        let cleaned = self.builder.clean(html).to_string();
+        let stripped = stripped_markup(html, &cleaned);
+
+        (cleaned, stripped)
+    }
+}
+
+type MarkupCounts = BTreeMap<(String, Option<String>), usize>;
+
+// Ammonia does not tell what it removed, so the elements and attributes
+// of the html are counted before and after cleaning. The attributes of
+// elements removed altogether are not worth telling.
+fn stripped_markup(before: &str, after: &str) -> Vec<Stripped> {
+    let after = count_markup(after);
+
+    count_markup(before)
+        .into_iter()
+        .filter(|((tag, attribute), _)| {
+            attribute.is_none() || after.contains_key(&(tag.clone(), None))
+        })
+        .filter_map(|((tag, attribute), count)| {
+            let kept = after
+                .get(&(tag.clone(), attribute.clone()))
+                .copied()
+                .unwrap_or(0);
+
+            (count > kept).then(|| Stripped {
+                tag,
+                attribute,
+                count: count - kept,
+            })
+        })
+        .collect()
+}
+
+// Opening tags and their attributes, by name. Comments count as a tag
+// of "#comment", as they are named in the DOM.
+fn count_markup(html: &str) -> MarkupCounts {
+    let mut counts = MarkupCounts::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+
+        if let Some(comment) = rest.strip_prefix("!--") {
+            *counts.entry(("#comment".to_string(), None)).or_default() += 1;
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+
+        if !rest.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            continue;
+        }
+
+        let name_len = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let tag = rest[..name_len].to_ascii_lowercase();
+        rest = &rest[name_len..];
+
+        for attribute in attribute_names(&mut rest) {
+            *counts.entry((tag.clone(), Some(attribute))).or_default() += 1;
+        }
+        *counts.entry((tag, None)).or_default() += 1;
+    }
+
+    counts
+}
+
+// Names of the attributes at the start of a tag, leaving rest after
+// the end of the tag.
+fn attribute_names(rest: &mut &str) -> Vec<String> {
+    let mut names = Vec::new();
+
+    loop {
+        *rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+
+        if rest.is_empty() {
+            return names;
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            *rest = after;
+            return names;
+        }
+
+        let name_len = rest
+            .find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        names.push(rest[..name_len].to_ascii_lowercase());
+        *rest = rest[name_len..].trim_start();
+
+        if let Some(value) = rest.strip_prefix('=') {
+            let value = value.trim_start();
+            let value_len = match value.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    value[1..].find(quote).map_or(value.len(), |end| end + 2)
+                }
+                _ => value
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .unwrap_or(value.len()),
+            };
+            *rest = &value[value_len..];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stripped(tag: &str, attribute: Option<&str>, count: usize) -> Stripped {
+        Stripped {
+            tag: tag.to_string(),
+            attribute: attribute.map(str::to_string),
+            count,
+        }
+    }
+
+    fn default_sanitizer() -> Sanitizer {
+        Sanitizer::new(&[], &[])
+    }
+
+    #[test]
+    fn removed_elements_are_reported() {
+        let (html, report) = default_sanitizer().clean("<p>a<script>alert(1)</script></p>");
+
+        assert_eq!(html, "<p>a</p>");
+        assert_eq!(report, vec![stripped("script", None, 1)]);
+    }
+
+    #[test]
+    fn removed_attributes_are_reported() {
+        let (html, report) =
+            default_sanitizer().clean("<p onclick=\"x()\">a</p><p onclick=y()>b</p>");
+
+        assert_eq!(html, "<p>a</p><p>b</p>");
+        assert_eq!(report, vec![stripped("p", Some("onclick"), 2)]);
+    }
+
+    #[test]
+    fn attributes_of_removed_elements_are_not_reported() {
+        let (_, report) = default_sanitizer().clean("<script src=\"x.js\"></script>");
+
+        assert_eq!(report, vec![stripped("script", None, 1)]);
+    }
+
+    #[test]
+    fn comments_are_reported() {
+        let (html, report) = default_sanitizer().clean("<p>a<!-- <b>not a tag</b> --></p>");
+
+        assert_eq!(html, "<p>a</p>");
+        assert_eq!(report, vec![stripped("#comment", None, 1)]);
+    }
+
+    #[test]
+    fn quoted_greater_than_does_not_end_a_tag() {
+        let (_, report) = default_sanitizer()
+            .clean("<p title=\"a > b\" onclick=\"x()\">a</p><p title='c > d'>b</p>");
+
+        assert_eq!(report, vec![stripped("p", Some("onclick"), 1)]);
+    }
+
+    #[test]
+    fn renderer_markup_is_kept() {
+        let html = "<h2 id=\"intro\">Intro</h2>\
+                    <pre><code class=\"language-rust\">fn main() {}</code></pre>\
+                    <ul><li><input type=\"checkbox\" disabled=\"\" checked=\"\"> done</li></ul>";
+
+        let (_, report) = default_sanitizer().clean(html);
+
+        assert_eq!(report, vec![]);
+    }
+
+    #[test]
+    fn only_checkbox_inputs_are_kept() {
+        let (html, report) = default_sanitizer().clean("<input type=\"text\">");
+
+        assert_eq!(html, "<input>");
+        assert_eq!(report, vec![stripped("input", Some("type"), 1)]);
+    }
+
+    #[test]
+    fn allowed_tags_are_kept() {
+        let sanitizer = Sanitizer::new(&["iframe".to_string()], &[]);
+
+        let (html, report) = sanitizer.clean("<iframe></iframe>");
+
+        assert_eq!(html, "<iframe></iframe>");
+        assert_eq!(report, vec![]);
+    }
+
+    // Ammonia removes script and style along with their content, and
+    // refuses to allow such a tag unless it stops doing so.
+    #[test]
+    fn allowed_tags_removed_with_their_content_are_kept() {
+        let sanitizer = Sanitizer::new(&["style".to_string()], &[]);
+
+        let (html, report) = sanitizer.clean("<style>p {}</style>");
+
+        assert_eq!(html, "<style>p {}</style>");
+        assert_eq!(report, vec![]);
+    }
+
+    #[test]
+    fn allowed_attributes_are_kept() {
+        let sanitizer = Sanitizer::new(
+            &["iframe".to_string()],
+            &[
+                ("iframe".to_string(), "src".to_string()),
+                ("*".to_string(), "data-note".to_string()),
+            ],
+        );
+
+        let (html, report) = sanitizer
+            .clean("<iframe src=\"https://example.com\"></iframe><p data-note=\"x\">a</p>");
+
+        assert_eq!(
+            html,
+            "<iframe src=\"https://example.com\"></iframe><p data-note=\"x\">a</p>"
+        );
+        assert_eq!(report, vec![]);
+    }
+
+    #[test]
+    fn attributes_of_tags_removed_with_their_content_can_be_allowed() {
+        let sanitizer = Sanitizer::new(&[], &[("script".to_string(), "src".to_string())]);
+
+        let (html, _) = sanitizer.clean("<p>a</p>");
+
+        assert_eq!(html, "<p>a</p>");
+    }
+
+    // Ammonia writes rel on links by itself, and refuses to allow it
+    // unless it stops doing so.
+    #[test]
+    fn rel_can_be_allowed() {
+        let sanitizer = Sanitizer::new(&[], &[("a".to_string(), "rel".to_string())]);
+
+        let (html, report) = sanitizer.clean("<a href=\"https://example.com\" rel=\"me\">a</a>");
+
+        assert_eq!(html, "<a href=\"https://example.com\" rel=\"me\">a</a>");
+        assert_eq!(report, vec![]);
+    }
+
+    #[test]
+    fn rel_is_written_on_links_unless_allowed() {
+        let (html, _) = default_sanitizer().clean("<a href=\"https://example.com\">a</a>");
+
+        assert_eq!(
+            html,
+            "<a href=\"https://example.com\" rel=\"noopener noreferrer\">a</a>"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use serde::Serialize;
+
+use super::{unescape_html, Anchors};
+
+#[derive(Serialize)]
+pub struct TocEntry {
+    pub level: u8,
+    pub id: String,
+    pub text: String,
+    // Subsections, one level deeper.
+    pub entries: Vec<TocEntry>,
+}
+
+struct Heading<'a> {
+    level: u8,
+    // Position of the end of the opening tag.
+    open_end: usize,
+    id: Option<&'a str>,
+    inner: &'a str,
+}
+
+// Every h2 to h4 heading of some html, in order. These make up the
+// table of contents, h1 is left to the title of the post.
+fn headings(html: &str) -> Vec<Heading<'_>> {
+    let mut headings = Vec::new();
+    let mut from = 0;
+
+    while let Some(pos) = html[from..].find("<h") {
+        let start = from + pos;
+        from = start + 2;
+
+        let level = match html.as_bytes().get(start + 2) {
+            Some(c @ b'2'..=b'4') => c - b'0',
+            _ => continue,
+        };
+        if !matches!(html.as_bytes().get(start + 3), Some(b'>' | b' ')) {
+            continue;
+        }
+
+        let Some(open_len) = html[start..].find('>') else {
+            break;
+        };
+        let open_end = start + open_len;
+
+        let close = format!("</h{}>", level);
+        let Some(inner_len) = html[open_end + 1..].find(&close) else {
+            continue;
+        };
+        let inner = &html[open_end + 1..open_end + 1 + inner_len];
+
+        headings.push(Heading {
+            level,
+            open_end,
+            id: attribute(&html[start..open_end], "id"),
+            inner,
+        });
+        from = open_end + 1 + inner_len + close.len();
+    }
+
+    headings
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!(" {}=\"", name);
+    let start = tag.find(&pattern)? + pattern.len();
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+// The text of a heading, without its markup.
+fn heading_text(inner: &str) -> String {
+    let mut text = String::with_capacity(inner.len());
+    let mut in_tag = false;
+
+    for c in inner.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    unescape_html(text.trim())
+}
+
+// Give every h2 to h4 heading without an id one made from its text,
+// so that the table of contents can link to it. Html written by hand
+// has none, markdown and org already have them.
+pub fn add_heading_ids(html: &str) -> String {
+    let headings = headings(html);
+    if headings.iter().all(|heading| heading.id.is_some()) {
+        return html.to_string();
+    }
+
+    let mut anchors = Anchors::default();
+    for id in headings.iter().filter_map(|heading| heading.id) {
+        anchors.reserve(id);
+    }
+
+    let mut with_ids = String::with_capacity(html.len() + headings.len() * 16);
+    let mut copied = 0;
+
+    for heading in headings.iter().filter(|heading| heading.id.is_none()) {
+        let id = anchors.unique(&heading_text(heading.inner));
+
+        with_ids.push_str(&html[copied..heading.open_end]);
+        with_ids.push_str(&format!(" id=\"{}\"", id));
+        copied = heading.open_end;
+    }
+
+    with_ids.push_str(&html[copied..]);
+    with_ids
+}
+
+// The headings of some html nested by level. Headings without an id
+// cannot be linked to and are left out.
+pub fn table_of_contents(html: &str) -> Vec<TocEntry> {
+    let mut toc: Vec<TocEntry> = Vec::new();
+
+    for heading in headings(html) {
+        let Some(id) = heading.id else {
+            continue;
+        };
+
+        // A heading goes under the last heading of a higher level, or
+        // at the top when there is none.
+        let mut entries = &mut toc;
+        while entries
+            .last()
+            .is_some_and(|last| last.level < heading.level)
+        {
+            entries = &mut entries.last_mut().unwrap().entries;
+        }
+
+        entries.push(TocEntry {
+            level: heading.level,
+            id: id.to_string(),
+            text: heading_text(heading.inner),
+            entries: Vec::new(),
+        });
+    }
+
+    toc
+}
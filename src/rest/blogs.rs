@@ -1,16 +1,19 @@
-use axum::extract::Path;
+use axum::extract::{Path, Query};
 use axum::http::StatusCode;
 use axum::routing::{get, patch, post, put};
 use axum::{extract::State, Router};
 use axum::{middleware, Extension, Json};
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
 
 use crate::app::AppState;
+use crate::data::blogs::{BlogStatus, ContentFormat, TocMode};
 use crate::data::{blogs, tags};
+use crate::render::org;
 
 use super::errors::ApiError;
-use super::helpers::{get_conn_from_pool, get_tx_from_pool};
+use super::helpers::{get_conn_from_pool, get_sanitizer_for_user, get_tx_from_pool, Sanitized};
 
 use super::middlewares::auth;
 use super::Result;
@@ -24,6 +27,8 @@ pub fn routes(state: AppState) -> Router {
                 .delete(delete_blog_handler)
                 .get(show_blog_handler),
         )
+        .route("/blog/:id/publish", post(publish_blog_handler))
+        .route("/blog/:id/unpublish", post(unpublish_blog_handler))
         .route("/blogs/", get(show_all_simple_blogs_handler))
         .route("/force-blog/", post(force_create_blog_handler))
         .route("/force-blog/:id", put(force_update_blog_handler))
@@ -34,10 +39,24 @@ pub fn routes(state: AppState) -> Router {
 #[derive(Serialize, Deserialize)]
 struct NewBlogWithTags {
     url: String,
-    title: String,
+    // Org content may leave the title, tags and create_time out, they
+    // are then taken from #+TITLE, #+FILETAGS and #+DATE.
+    title: Option<String>,
     preview: String,
     content: String,
-    tags: Vec<String>,
+    // Content is html unless stated otherwise.
+    #[serde(default)]
+    content_format: ContentFormat,
+    // Long posts get a table of contents unless stated otherwise.
+    #[serde(default)]
+    toc: TocMode,
+    tags: Option<Vec<String>>,
+    create_time: Option<DateTime<Utc>>,
+    // New blogs are drafts unless stated otherwise.
+    status: Option<BlogStatus>,
+    // A draft with publish_at is published by the publisher once
+    // the time has come.
+    publish_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -46,7 +65,11 @@ struct UpdatedBlogWithTags {
     title: Option<String>,
     preview: Option<String>,
     content: Option<String>,
+    content_format: Option<ContentFormat>,
+    toc: Option<TocMode>,
     tags: Option<Vec<String>>,
+    status: Option<BlogStatus>,
+    publish_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Deserialize)]
@@ -55,16 +78,21 @@ struct ForceFullBlog {
     title: String,
     preview: String,
     content: String,
+    content_format: Option<ContentFormat>,
     create_time: String,
     edit_time: String,
+    #[serde(default)]
     tags: Vec<String>,
+    // Force created blogs are usually imported, they are published
+    // unless stated otherwise.
+    status: Option<BlogStatus>,
 }
 
 async fn create_blog_handler(
     State(state): State<AppState>,
     Extension(user_id): Extension<i64>,
     Json(new_blog_with_tags): Json<NewBlogWithTags>,
-) -> Result<(StatusCode, Json<blogs::FullBlog>)> {
+) -> Result<(StatusCode, Json<Sanitized<blogs::FullBlog>>)> {
     // Here we must use begin.
     // If any part of the insertions failed, discard all the changes.
     // We only need to commit the successful changes.
@@ -72,20 +100,40 @@ async fn create_blog_handler(
     // automatically be called.
     let mut tx = get_tx_from_pool(state.db).await?;
 
+    check_url_is_free(&new_blog_with_tags.url, None, &mut tx).await?;
+
+    let keywords = match new_blog_with_tags.content_format {
+        ContentFormat::Org => org::keywords(&new_blog_with_tags.content),
+        _ => org::OrgKeywords::default(),
+    };
+
+    let title = new_blog_with_tags
+        .title
+        .or(keywords.title)
+        .ok_or(ApiError::MissingField("title"))?;
+    let tags = new_blog_with_tags.tags.unwrap_or(keywords.tags);
+    let create_time = new_blog_with_tags.create_time.or(keywords.date);
+
     let new_blog = blogs::NewBlog::new(
         user_id,
         new_blog_with_tags.url,
-        new_blog_with_tags.title,
+        title,
         new_blog_with_tags.preview,
         new_blog_with_tags.content,
+        new_blog_with_tags.content_format,
+        new_blog_with_tags.toc,
+        create_time,
+        new_blog_with_tags.status.unwrap_or(BlogStatus::Draft),
+        new_blog_with_tags.publish_at,
     );
 
-    let blog = blogs::create_blog(new_blog, &mut tx)
+    let sanitizer = get_sanitizer_for_user(&state.sanitizer, user_id, &mut tx).await?;
+
+    let (blog, stripped) = blogs::create_blog(new_blog, sanitizer, &mut tx)
         .await
         .map_err(ApiError::SqlxError)?;
 
-    let tags = new_blog_with_tags.tags;
-    tags::create_some_tags(&tags, blog.id, &mut tx)
+    let tags = tags::create_some_tags(&tags, blog.id, &mut tx)
         .await
         .map_err(ApiError::SqlxError)?;
 
@@ -93,16 +141,33 @@ async fn create_blog_handler(
 
     Ok((
         StatusCode::CREATED,
-        Json(blogs::FullBlog::from_blog_and_tags(blog, tags)),
+        Json(Sanitized {
+            body: blogs::FullBlog::from_blog_and_tags(blog, tags),
+            stripped,
+        }),
     ))
 }
 
+// Old urls keep redirecting to their blog, they cannot be reused by
+// another one.
+async fn check_url_is_free(url: &str, blog_id: Option<i64>, conn: &mut PgConnection) -> Result<()> {
+    let reserved = blogs::is_url_reserved(url, blog_id, conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    if reserved {
+        return Err(ApiError::ReservedUrl(url.to_string()));
+    }
+
+    Ok(())
+}
+
 async fn update_blog_handler(
     State(state): State<AppState>,
     Extension(user_id): Extension<i64>,
     Path(id): Path<i64>,
     Json(updated_blog_with_tags): Json<UpdatedBlogWithTags>,
-) -> Result<StatusCode> {
+) -> Result<Json<Sanitized<()>>> {
     if id < 0 {
         return Err(ApiError::NotFound);
     }
@@ -118,11 +183,26 @@ async fn update_blog_handler(
     }
 
     blog.url = updated_blog_with_tags.url.unwrap_or(blog.url);
+    check_url_is_free(&blog.url, Some(id), &mut tx).await?;
     blog.title = updated_blog_with_tags.title.unwrap_or(blog.title);
     blog.preview = updated_blog_with_tags.preview.unwrap_or(blog.preview);
     blog.content = updated_blog_with_tags.content.unwrap_or(blog.content);
+    blog.content_format = updated_blog_with_tags
+        .content_format
+        .unwrap_or(blog.content_format);
+    blog.toc = updated_blog_with_tags.toc.unwrap_or(blog.toc);
+    blog.status = updated_blog_with_tags.status.unwrap_or(blog.status);
+
+    if updated_blog_with_tags.publish_at.is_some() {
+        blog.publish_at = updated_blog_with_tags.publish_at;
+    } else if updated_blog_with_tags.status == Some(BlogStatus::Draft) {
+        // Moving a blog back to draft cancels its schedule.
+        blog.publish_at = None;
+    }
+
+    let sanitizer = get_sanitizer_for_user(&state.sanitizer, user_id, &mut tx).await?;
 
-    let _blog = blogs::update_blog(blog, &mut tx)
+    let (_blog, stripped) = blogs::update_blog(blog, sanitizer, &mut tx)
         .await
         .map_err(ApiError::SqlxError)?;
 
@@ -139,11 +219,12 @@ async fn update_blog_handler(
 
     tx.commit().await.map_err(ApiError::SqlxError)?;
 
-    Ok(StatusCode::NO_CONTENT)
+    Ok(Json(Sanitized { body: (), stripped }))
 }
 
 async fn show_blog_handler(
     State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
     Path(id): Path<i64>,
 ) -> Result<Json<blogs::FullBlog>> {
     if id < 0 {
@@ -156,6 +237,10 @@ async fn show_blog_handler(
         .await
         .map_err(ApiError::SqlxError)?;
 
+    if !blog.is_readable_by(user_id) {
+        return Err(ApiError::NotFound);
+    }
+
     Ok(Json(blog))
 }
 
@@ -189,16 +274,175 @@ async fn delete_blog_handler(
     }
 }
 
+pub(super) const DEFAULT_PAGE_LIMIT: i64 = 20;
+pub(super) const MAX_PAGE_LIMIT: i64 = 100;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum TagMode {
+    #[default]
+    Any,
+    All,
+}
+
+#[derive(Deserialize)]
+struct ListBlogsQuery {
+    limit: Option<i64>,
+    cursor: Option<String>,
+    // Comma separated tag names.
+    tags: Option<String>,
+    #[serde(default)]
+    tag_mode: TagMode,
+    user_id: Option<i64>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    edited_after: Option<DateTime<Utc>>,
+    edited_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    order: blogs::SortOrder,
+}
+
+#[derive(Serialize)]
+struct BlogsPage {
+    blogs: Vec<blogs::SimpleBlog>,
+    next_cursor: Option<String>,
+    total: i64,
+}
+
+// Tags are given in a query as comma separated names.
+pub(super) fn split_tags(tags: Option<String>) -> Vec<String> {
+    match tags {
+        Some(s) => s
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+// A cursor is opaque to clients, it is the hex encoded
+// "<create_time>,<id>" of the last blog of a page.
+fn encode_cursor(cursor: blogs::BlogCursor) -> String {
+    hex::encode(format!("{},{}", cursor.create_time.timestamp(), cursor.id))
+}
+
+fn decode_cursor(s: &str) -> Result<blogs::BlogCursor> {
+    let bytes = hex::decode(s).map_err(|_| ApiError::InvalidCursor)?;
+    let decoded = String::from_utf8(bytes).map_err(|_| ApiError::InvalidCursor)?;
+
+    let (timestamp, id) = decoded.split_once(',').ok_or(ApiError::InvalidCursor)?;
+    let timestamp = timestamp
+        .parse::<i64>()
+        .map_err(|_| ApiError::InvalidCursor)?;
+    let id = id.parse::<i64>().map_err(|_| ApiError::InvalidCursor)?;
+
+    let naive_time =
+        NaiveDateTime::from_timestamp_opt(timestamp, 0).ok_or(ApiError::InvalidCursor)?;
+    let create_time = DateTime::<Utc>::from_utc(naive_time, Utc);
+
+    Ok(blogs::BlogCursor { create_time, id })
+}
+
 async fn show_all_simple_blogs_handler(
     State(state): State<AppState>,
-) -> Result<Json<Vec<blogs::SimpleBlog>>> {
+    Extension(user_id): Extension<i64>,
+    Query(query): Query<ListBlogsQuery>,
+) -> Result<Json<BlogsPage>> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT);
+
+    let cursor = match query.cursor {
+        Some(s) => Some(decode_cursor(&s)?),
+        None => None,
+    };
+
+    let filter = blogs::BlogFilter {
+        viewer_id: Some(user_id),
+        user_id: query.user_id,
+        tags: split_tags(query.tags),
+        match_all_tags: matches!(query.tag_mode, TagMode::All),
+        created_after: query.created_after,
+        created_before: query.created_before,
+        edited_after: query.edited_after,
+        edited_before: query.edited_before,
+    };
+
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    // One more than asked, to know whether there is a next page.
+    let mut blogs =
+        blogs::get_simple_blogs_page(&filter, query.order, cursor, limit + 1, &mut conn)
+            .await
+            .map_err(ApiError::SqlxError)?;
+
+    let next_cursor = if blogs.len() as i64 > limit {
+        blogs.truncate(limit as usize);
+        blogs.last().map(|blog| {
+            encode_cursor(blogs::BlogCursor {
+                create_time: blog.create_time,
+                id: blog.id,
+            })
+        })
+    } else {
+        None
+    };
+
+    let total = blogs::count_simple_blogs(&filter, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    Ok(Json(BlogsPage {
+        blogs,
+        next_cursor,
+        total,
+    }))
+}
+
+async fn publish_blog_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode> {
+    change_blog_status(state, user_id, id, BlogStatus::Published).await
+}
+
+async fn unpublish_blog_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode> {
+    change_blog_status(state, user_id, id, BlogStatus::Draft).await
+}
+
+async fn change_blog_status(
+    state: AppState,
+    user_id: i64,
+    id: i64,
+    status: BlogStatus,
+) -> Result<StatusCode> {
+    if id < 0 {
+        return Err(ApiError::NotFound);
+    }
+
     let mut conn = get_conn_from_pool(state.db).await?;
 
-    let blogs = blogs::get_all_simple_blogs(&mut conn)
+    let blog_user_id = blogs::get_user_id_with_blog_id(id, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    if blog_user_id != user_id {
+        return Err(ApiError::Unauthorized);
+    }
+
+    // Publishing or unpublishing by hand overrides any schedule.
+    let _blog = blogs::set_blog_status(id, status, None, &mut conn)
         .await
         .map_err(ApiError::SqlxError)?;
 
-    Ok(Json(blogs))
+    Ok(StatusCode::NO_CONTENT)
 }
 
 fn parse_time_string(str: String) -> Result<DateTime<Utc>> {
@@ -218,9 +462,11 @@ async fn force_create_blog_handler(
     State(state): State<AppState>,
     Extension(user_id): Extension<i64>,
     Json(new_blog_with_tags): Json<ForceFullBlog>,
-) -> Result<(StatusCode, Json<blogs::FullBlog>)> {
+) -> Result<(StatusCode, Json<Sanitized<blogs::FullBlog>>)> {
     let mut tx = get_tx_from_pool(state.db).await?;
 
+    check_url_is_free(&new_blog_with_tags.url, None, &mut tx).await?;
+
     let create_time = parse_time_string(new_blog_with_tags.create_time)?;
 
     let edit_time = parse_time_string(new_blog_with_tags.edit_time)?;
@@ -231,16 +477,20 @@ async fn force_create_blog_handler(
         new_blog_with_tags.title,
         new_blog_with_tags.preview,
         new_blog_with_tags.content,
+        new_blog_with_tags.content_format.unwrap_or_default(),
         create_time,
         edit_time,
+        new_blog_with_tags.status.unwrap_or(BlogStatus::Published),
     );
 
-    let blog = blogs::force_create_blog(new_blog, &mut tx)
+    let sanitizer = get_sanitizer_for_user(&state.sanitizer, user_id, &mut tx).await?;
+
+    let (blog, stripped) = blogs::force_create_blog(new_blog, sanitizer, &mut tx)
         .await
         .map_err(ApiError::SqlxError)?;
 
     let tags = new_blog_with_tags.tags;
-    tags::create_some_tags(&tags, blog.id, &mut tx)
+    let tags = tags::create_some_tags(&tags, blog.id, &mut tx)
         .await
         .map_err(ApiError::SqlxError)?;
 
@@ -248,7 +498,10 @@ async fn force_create_blog_handler(
 
     Ok((
         StatusCode::CREATED,
-        Json(blogs::FullBlog::from_blog_and_tags(blog, tags)),
+        Json(Sanitized {
+            body: blogs::FullBlog::from_blog_and_tags(blog, tags),
+            stripped,
+        }),
     ))
 }
 
@@ -257,7 +510,7 @@ async fn force_update_blog_handler(
     Extension(user_id): Extension<i64>,
     Path(id): Path<i64>,
     Json(updated_blog_with_tags): Json<ForceFullBlog>,
-) -> Result<StatusCode> {
+) -> Result<Json<Sanitized<()>>> {
     if id < 0 {
         return Err(ApiError::NotFound);
     }
@@ -273,13 +526,20 @@ async fn force_update_blog_handler(
     }
 
     blog.url = updated_blog_with_tags.url;
+    check_url_is_free(&blog.url, Some(id), &mut tx).await?;
     blog.title = updated_blog_with_tags.title;
     blog.preview = updated_blog_with_tags.preview;
     blog.content = updated_blog_with_tags.content;
+    blog.content_format = updated_blog_with_tags
+        .content_format
+        .unwrap_or(blog.content_format);
     blog.create_time = parse_time_string(updated_blog_with_tags.create_time)?;
     blog.edit_time = parse_time_string(updated_blog_with_tags.edit_time)?;
+    blog.status = updated_blog_with_tags.status.unwrap_or(blog.status);
 
-    let _blog = blogs::force_update_blog(blog, &mut tx)
+    let sanitizer = get_sanitizer_for_user(&state.sanitizer, user_id, &mut tx).await?;
+
+    let (_blog, stripped) = blogs::force_update_blog(blog, sanitizer, &mut tx)
         .await
         .map_err(ApiError::SqlxError)?;
 
@@ -293,5 +553,5 @@ async fn force_update_blog_handler(
 
     tx.commit().await.map_err(ApiError::SqlxError)?;
 
-    Ok(StatusCode::NO_CONTENT)
+    Ok(Json(Sanitized { body: (), stripped }))
 }
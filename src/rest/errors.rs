@@ -10,7 +10,12 @@ pub enum ApiError {
     NoAuthorizationHeader,
     ExpiredToken,
     DuplicatedUsername(String),
+    ReservedUrl(String),
+    Conflict(String),
+    BadReference(String),
     InvalidTimeString(chrono::ParseError),
+    InvalidCursor,
+    MissingField(&'static str),
     InternalServerError(String),
 }
 
@@ -31,6 +36,27 @@ fn error_response<S: AsRef<str>>(
     )
 }
 
+// Postgres error codes of the constraints we care about.
+const UNIQUE_VIOLATION: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+
+fn constraint_message(constraint: Option<&str>) -> String {
+    match constraint {
+        Some("users_username_key") => "username already exists".to_string(),
+        Some("blogs_url_key") => "url already exists".to_string(),
+        Some("blog_tags_pkey") => "the same tag is attached twice".to_string(),
+        Some("tags_name_key") => "tag already exists".to_string(),
+        Some("tags_slug_key") => "tag slug already exists".to_string(),
+        Some("blogs_user_id_fkey") | Some("tokens_user_id_fkey") => {
+            "the user does not exist".to_string()
+        }
+        Some("blog_tags_blog_id_fkey") => "the blog does not exist".to_string(),
+        Some("blog_tags_tag_id_fkey") => "the tag does not exist".to_string(),
+        Some(c) => format!("violates constraint {}", c),
+        None => "violates a constraint".to_string(),
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> axum::response::Response {
         match self {
@@ -40,6 +66,20 @@ impl IntoResponse for ApiError {
                     "the requested resource could not be found",
                 )
                 .into_response(),
+                sqlx::Error::Database(db_err) => match db_err.code().as_deref() {
+                    Some(UNIQUE_VIOLATION) => {
+                        Self::Conflict(constraint_message(db_err.constraint())).into_response()
+                    }
+                    Some(FOREIGN_KEY_VIOLATION) => {
+                        Self::BadReference(constraint_message(db_err.constraint())).into_response()
+                    }
+                    _ => {
+                        info!("{:?}", db_err);
+
+                        error_response(StatusCode::INTERNAL_SERVER_ERROR, "internal server error")
+                            .into_response()
+                    }
+                },
                 _ => {
                     info!("{:?}", err);
 
@@ -77,10 +117,27 @@ impl IntoResponse for ApiError {
                 format!("username {} already exists", username).as_str(),
             )
             .into_response(),
+            Self::ReservedUrl(url) => error_response(
+                StatusCode::CONFLICT,
+                format!("url {} is reserved by another blog", url).as_str(),
+            )
+            .into_response(),
+            Self::Conflict(s) => error_response(StatusCode::CONFLICT, s).into_response(),
+            Self::BadReference(s) => {
+                error_response(StatusCode::UNPROCESSABLE_ENTITY, s).into_response()
+            }
             Self::InvalidTimeString(err) => {
                 info!("{:?}", err);
                 error_response(StatusCode::BAD_REQUEST, "invalid time string").into_response()
             }
+            Self::InvalidCursor => {
+                error_response(StatusCode::BAD_REQUEST, "invalid cursor").into_response()
+            }
+            Self::MissingField(field) => error_response(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("{} is required", field),
+            )
+            .into_response(),
             Self::InternalServerError(s) => {
                 error!("{}", s);
                 error_response(StatusCode::INTERNAL_SERVER_ERROR, s).into_response()
@@ -1,6 +1,9 @@
 use super::errors::ApiError;
 use super::Result;
-use sqlx::{pool::PoolConnection, PgPool, Postgres, Transaction};
+use crate::data::users::{self, TrustLevel};
+use crate::render::sanitize::{SanitizeReport, Sanitizer};
+use serde::Serialize;
+use sqlx::{pool::PoolConnection, PgConnection, PgPool, Postgres, Transaction};
 
 pub async fn get_conn_from_pool(pool: PgPool) -> Result<PoolConnection<Postgres>> {
     let conn = pool.acquire().await.map_err(ApiError::SqlxError)?;
@@ -12,3 +15,41 @@ pub async fn get_tx_from_pool(pool: PgPool) -> Result<Transaction<'static, Postg
 
     Ok(tx)
 }
+
+// The html of untrusted users goes through the sanitizer, trusted
+// users have none.
+pub async fn get_sanitizer_for_user<'a>(
+    sanitizer: &'a Sanitizer,
+    user_id: i64,
+    conn: &mut PgConnection,
+) -> Result<Option<&'a Sanitizer>> {
+    let user = users::get_user(user_id, conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    match user.trust_level {
+        TrustLevel::Trusted => Ok(None),
+        TrustLevel::Untrusted => Ok(Some(sanitizer)),
+    }
+}
+
+// Changes reaching the blogs of every user are left to trusted users.
+pub async fn check_user_is_trusted(user_id: i64, conn: &mut PgConnection) -> Result<()> {
+    let user = users::get_user(user_id, conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    match user.trust_level {
+        TrustLevel::Trusted => Ok(()),
+        TrustLevel::Untrusted => Err(ApiError::Unauthorized),
+    }
+}
+
+// Sent back whenever a blog is written, so that the writer knows what
+// did not make it to the site.
+#[derive(Serialize)]
+pub struct Sanitized<T: Serialize> {
+    #[serde(flatten)]
+    pub body: T,
+    pub stripped: SanitizeReport,
+}
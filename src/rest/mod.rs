@@ -2,6 +2,10 @@ mod blogs;
 mod errors;
 mod helpers;
 mod middlewares;
+mod revisions;
+mod search;
+mod tags;
+mod trash;
 mod users;
 
 use super::AppState;
@@ -13,7 +17,11 @@ pub type Result<T, E = ApiError> = std::result::Result<T, E>;
 pub fn routes(state: AppState) -> Router {
     let r = Router::new()
         .merge(users::routes(state.clone()))
-        .merge(blogs::routes(state));
+        .merge(blogs::routes(state.clone()))
+        .merge(revisions::routes(state.clone()))
+        .merge(search::routes(state.clone()))
+        .merge(tags::routes(state.clone()))
+        .merge(trash::routes(state));
 
     Router::new().nest("/api", r)
 }
@@ -0,0 +1,238 @@
+use axum::extract::{Path, Query, State};
+use axum::routing::{get, post};
+use axum::{middleware, Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use sqlx::PgConnection;
+
+use crate::app::AppState;
+use crate::data::{blogs, revisions, tags};
+
+use super::errors::ApiError;
+use super::helpers::{get_conn_from_pool, get_sanitizer_for_user, get_tx_from_pool, Sanitized};
+use super::middlewares::auth;
+use super::Result;
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/blog/:id/revisions", get(list_revisions_handler))
+        .route("/blog/:id/revisions/diff", get(diff_revisions_handler))
+        .route("/blog/:id/revisions/:version", get(show_revision_handler))
+        .route(
+            "/blog/:id/revisions/:version/restore",
+            post(restore_revision_handler),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth))
+        .with_state(state)
+}
+
+async fn check_blog_owner(id: i64, user_id: i64, conn: &mut PgConnection) -> Result<()> {
+    if id < 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    let blog_user_id = blogs::get_user_id_with_blog_id(id, conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    if blog_user_id != user_id {
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(())
+}
+
+async fn list_revisions_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<revisions::SimpleRevision>>> {
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    check_blog_owner(id, user_id, &mut conn).await?;
+
+    let revisions = revisions::get_all_simple_revisions(id, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    Ok(Json(revisions))
+}
+
+async fn show_revision_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path((id, version)): Path<(i64, i64)>,
+) -> Result<Json<revisions::Revision>> {
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    check_blog_owner(id, user_id, &mut conn).await?;
+
+    let revision = revisions::get_revision(id, version, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    Ok(Json(revision))
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    from: i64,
+    // Defaults to the current version of the blog.
+    to: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct DiffLine {
+    op: &'static str,
+    old_line: Option<usize>,
+    new_line: Option<usize>,
+    value: String,
+}
+
+#[derive(Serialize)]
+struct RevisionDiff {
+    from: i64,
+    to: i64,
+    title: Vec<Vec<DiffLine>>,
+    preview: Vec<Vec<DiffLine>>,
+    content: Vec<Vec<DiffLine>>,
+}
+
+struct RevisionText {
+    title: String,
+    preview: String,
+    content: String,
+}
+
+// Line diff of two texts, grouped into hunks with 3 lines of context.
+fn diff_lines(old: &str, new: &str) -> Vec<Vec<DiffLine>> {
+    let diff = TextDiff::from_lines(old, new);
+
+    diff.grouped_ops(3)
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| DiffLine {
+                    op: match change.tag() {
+                        ChangeTag::Equal => "equal",
+                        ChangeTag::Delete => "delete",
+                        ChangeTag::Insert => "insert",
+                    },
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                    value: change.to_string_lossy().into_owned(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+async fn get_revision_text(
+    blog: &blogs::Blog,
+    version: i64,
+    conn: &mut PgConnection,
+) -> Result<RevisionText> {
+    if version == blog.version() {
+        return Ok(RevisionText {
+            title: blog.title.clone(),
+            preview: blog.preview.clone(),
+            content: blog.content.clone(),
+        });
+    }
+
+    let revision = revisions::get_revision(blog.id, version, conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    Ok(RevisionText {
+        title: revision.title,
+        preview: revision.preview,
+        content: revision.content,
+    })
+}
+
+async fn diff_revisions_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path(id): Path<i64>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<RevisionDiff>> {
+    if id < 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let blog = blogs::get_blog(id, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    if blog.user_id != user_id {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let to = query.to.unwrap_or(blog.version());
+
+    let old = get_revision_text(&blog, query.from, &mut conn).await?;
+    let new = get_revision_text(&blog, to, &mut conn).await?;
+
+    Ok(Json(RevisionDiff {
+        from: query.from,
+        to,
+        title: diff_lines(&old.title, &new.title),
+        preview: diff_lines(&old.preview, &new.preview),
+        content: diff_lines(&old.content, &new.content),
+    }))
+}
+
+// Restoring writes the old title, preview, content and tags as a new
+// version, so the current version becomes a revision itself. The url
+// and status are left untouched.
+async fn restore_revision_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path((id, version)): Path<(i64, i64)>,
+) -> Result<Json<Sanitized<()>>> {
+    if id < 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    let mut tx = get_tx_from_pool(state.db).await?;
+
+    let mut blog = blogs::get_blog(id, &mut tx)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    if blog.user_id != user_id {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let revision = revisions::get_revision(id, version, &mut tx)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    blog.title = revision.title;
+    blog.preview = revision.preview;
+    blog.content = revision.content;
+    blog.content_format = revision.content_format;
+
+    let sanitizer = get_sanitizer_for_user(&state.sanitizer, user_id, &mut tx).await?;
+
+    let (_blog, stripped) = blogs::update_blog(blog, sanitizer, &mut tx)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    tags::delete_all_tags_for_blog_id(id, &mut tx)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    tags::create_some_tags(&revision.tags, id, &mut tx)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    tx.commit().await.map_err(ApiError::SqlxError)?;
+
+    Ok(Json(Sanitized { body: (), stripped }))
+}
@@ -0,0 +1,85 @@
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::{middleware, Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::app::AppState;
+use crate::data::blogs;
+
+use super::blogs::{split_tags, TagMode, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+use super::errors::ApiError;
+use super::helpers::get_conn_from_pool;
+use super::middlewares::auth;
+use super::Result;
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/search", get(search_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth))
+        .with_state(state)
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: Option<String>,
+    // Comma separated tag names.
+    tags: Option<String>,
+    #[serde(default)]
+    tag_mode: TagMode,
+    limit: Option<i64>,
+    // Results are ranked, so they are paged by number instead of by
+    // cursor. Starts at 1.
+    page: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct SearchPage {
+    results: Vec<blogs::SearchResult>,
+    page: i64,
+    next_page: Option<i64>,
+    total: i64,
+}
+
+async fn search_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<SearchPage>> {
+    let search = query
+        .q
+        .filter(|q| !q.trim().is_empty())
+        .ok_or(ApiError::MissingField("q"))?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .clamp(1, MAX_PAGE_LIMIT);
+    let page = query.page.unwrap_or(1).max(1);
+
+    let filter = blogs::BlogFilter {
+        viewer_id: Some(user_id),
+        tags: split_tags(query.tags),
+        match_all_tags: matches!(query.tag_mode, TagMode::All),
+        ..Default::default()
+    };
+
+    // Pages too far to be counted cannot have any result.
+    let offset = (page - 1).checked_mul(limit).ok_or(ApiError::NotFound)?;
+
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let results = blogs::search_blogs(&search, &filter, limit, offset, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    let total = blogs::count_search_results(&search, &filter, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    Ok(Json(SearchPage {
+        results,
+        page,
+        next_page: (limit < total - offset).then_some(page + 1),
+        total,
+    }))
+}
@@ -0,0 +1,191 @@
+use axum::extract::{Path, Query, State};
+use axum::routing::{get, patch, post};
+use axum::{middleware, Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+
+use crate::app::AppState;
+use crate::data::{blogs, tags};
+
+use super::errors::ApiError;
+use super::helpers::{check_user_is_trusted, get_conn_from_pool, get_tx_from_pool};
+use super::middlewares::auth;
+use super::Result;
+
+// Tags are shared by every blog of the site, changing one changes it
+// on every blog having it, so only trusted users may.
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/tags/", get(list_tags_handler))
+        .route(
+            "/tags/:name",
+            patch(update_tag_handler).delete(delete_tag_handler),
+        )
+        .route("/tags/:name/merge", post(merge_tags_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth))
+        .with_state(state)
+}
+
+#[derive(Deserialize)]
+struct ListTagsQuery {
+    #[serde(default)]
+    order: tags::TagOrder,
+}
+
+// Every tag with its number of blogs, counting the drafts of the user
+// too.
+async fn list_tags_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Query(query): Query<ListTagsQuery>,
+) -> Result<Json<Vec<tags::TagSummary>>> {
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let filter = blogs::BlogFilter {
+        viewer_id: Some(user_id),
+        ..Default::default()
+    };
+
+    let summaries = tags::get_tag_summaries(&filter, query.order, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    Ok(Json(summaries))
+}
+
+#[derive(Deserialize)]
+struct UpdatedTag {
+    // Renaming to a tag that already exists merges them.
+    name: Option<String>,
+    // An empty description removes it.
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MergedTags {
+    // Tags to merge into the one in the path.
+    tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct TagChange {
+    name: String,
+    slug: String,
+    description: Option<String>,
+    // Number of blogs whose tags were changed.
+    changed_blogs: i64,
+}
+
+async fn get_tag(name: &str, conn: &mut PgConnection) -> Result<tags::Tag> {
+    tags::get_tag_by_name(name, conn)
+        .await
+        .map_err(ApiError::SqlxError)?
+        .ok_or(ApiError::NotFound)
+}
+
+async fn update_tag_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path(name): Path<String>,
+    Json(updated_tag): Json<UpdatedTag>,
+) -> Result<Json<TagChange>> {
+    let mut tx = get_tx_from_pool(state.db).await?;
+
+    check_user_is_trusted(user_id, &mut tx).await?;
+
+    let mut tag = get_tag(&name, &mut tx).await?;
+    let mut changed_blogs = 0;
+
+    if let Some(new_name) = updated_tag.name {
+        let new_name = new_name.trim();
+        if new_name.is_empty() {
+            return Err(ApiError::MissingField("name"));
+        }
+
+        let existing = tags::get_tag_by_name(new_name, &mut tx)
+            .await
+            .map_err(ApiError::SqlxError)?;
+
+        changed_blogs = match existing {
+            Some(existing) if existing.id != tag.id => {
+                tags::merge_tags(&[name], new_name, &mut tx).await
+            }
+            _ => tags::rename_tag(tag.id, new_name, &mut tx).await,
+        }
+        .map_err(ApiError::SqlxError)?;
+
+        tag = get_tag(new_name, &mut tx).await?;
+    }
+
+    if let Some(description) = updated_tag.description {
+        let description = Some(description.trim()).filter(|d| !d.is_empty());
+        tags::set_tag_description(tag.id, description, &mut tx)
+            .await
+            .map_err(ApiError::SqlxError)?;
+        tag.description = description.map(str::to_string);
+    }
+
+    tx.commit().await.map_err(ApiError::SqlxError)?;
+
+    Ok(Json(TagChange {
+        name: tag.name,
+        slug: tag.slug,
+        description: tag.description,
+        changed_blogs,
+    }))
+}
+
+async fn merge_tags_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path(name): Path<String>,
+    Json(merged_tags): Json<MergedTags>,
+) -> Result<Json<TagChange>> {
+    if merged_tags.tags.is_empty() {
+        return Err(ApiError::MissingField("tags"));
+    }
+
+    let mut tx = get_tx_from_pool(state.db).await?;
+
+    check_user_is_trusted(user_id, &mut tx).await?;
+
+    let changed_blogs = tags::merge_tags(&merged_tags.tags, &name, &mut tx)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    let tag = get_tag(&name, &mut tx).await?;
+
+    tx.commit().await.map_err(ApiError::SqlxError)?;
+
+    Ok(Json(TagChange {
+        name: tag.name,
+        slug: tag.slug,
+        description: tag.description,
+        changed_blogs,
+    }))
+}
+
+async fn delete_tag_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path(name): Path<String>,
+) -> Result<Json<TagChange>> {
+    let mut tx = get_tx_from_pool(state.db).await?;
+
+    check_user_is_trusted(user_id, &mut tx).await?;
+
+    let tag = get_tag(&name, &mut tx).await?;
+
+    let changed_blogs = tags::delete_tag(tag.id, &mut tx)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    tx.commit().await.map_err(ApiError::SqlxError)?;
+
+    Ok(Json(TagChange {
+        name: tag.name,
+        slug: tag.slug,
+        description: None,
+        changed_blogs,
+    }))
+}
@@ -0,0 +1,100 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{delete, get, post};
+use axum::{middleware, Extension, Json, Router};
+use serde::Serialize;
+
+use crate::app::AppState;
+use crate::data::blogs;
+
+use super::errors::ApiError;
+use super::helpers::get_conn_from_pool;
+use super::middlewares::auth;
+use super::Result;
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route(
+            "/trash/",
+            get(list_trash_handler).delete(empty_trash_handler),
+        )
+        .route("/trash/:id", delete(purge_blog_handler))
+        .route("/trash/:id/restore", post(restore_blog_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth))
+        .with_state(state)
+}
+
+async fn list_trash_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+) -> Result<Json<Vec<blogs::TrashedBlog>>> {
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let blogs = blogs::get_all_trashed_blogs(user_id, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    Ok(Json(blogs))
+}
+
+async fn restore_blog_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode> {
+    if id < 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let success = blogs::restore_blog(id, user_id, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    if success {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+async fn purge_blog_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode> {
+    if id < 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let success = blogs::purge_blog(id, user_id, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    if success {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[derive(Serialize)]
+struct PurgeResult {
+    purged: u64,
+}
+
+async fn empty_trash_handler(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<i64>,
+) -> Result<Json<PurgeResult>> {
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let purged = blogs::purge_all_trashed_blogs(user_id, &mut conn)
+        .await
+        .map_err(ApiError::SqlxError)?;
+
+    Ok(Json(PurgeResult { purged }))
+}
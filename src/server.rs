@@ -1,8 +1,19 @@
 use std::net::SocketAddr;
 
 use crate::app::{self, AppState};
+use crate::publisher;
+use crate::web::theme;
 
 pub async fn serve(state: AppState) {
+    tokio::spawn(publisher::run(
+        state.db.clone(),
+        state.config.publish_interval,
+    ));
+
+    if state.config.dev {
+        tokio::spawn(theme::watch(state.theme.clone()));
+    }
+
     let app = app::create_app(state.clone());
 
     let addr = SocketAddr::from(([127, 0, 0, 1], state.config.socket));
@@ -1,35 +1,86 @@
 use axum::{
-    extract::{Path, State},
-    response::Html,
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse, Response},
     routing::get,
     Router,
 };
 use chrono::{DateTime, Utc};
-use serde::Serialize;
-use tinytemplate::TinyTemplate;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     app::AppState,
     data::{
-        blogs::{self, FullBlog, SimpleBlog},
-        tags::{self, get_all_tag_names},
+        blogs::{self, BlogFilter, FullBlog, SimpleBlog, TocMode},
+        tags::{self, TagOrder},
     },
+    render::toc::{self, TocEntry},
 };
 
-use super::{errors::WebError, helpers::get_conn_from_pool, Result};
+use super::{
+    errors::WebError,
+    helpers::{get_conn_from_pool, moved_permanently},
+    Result, Site,
+};
 
 pub fn routes(state: AppState) -> Router {
     Router::new()
         .route("/", get(show_home_handler))
+        .route("/page/:n", get(show_home_page_handler))
         .route("/posts/:url", get(show_blog_handler))
         .route("/tags/", get(list_tags_handler))
-        .route("/tags/:name", get(show_tag_handler))
+        .route("/tags/:slug", get(show_tag_handler))
+        .route("/tags/:slug/page/:n", get(show_tag_page_handler))
         .with_state(state)
 }
 
 #[derive(Serialize)]
-struct HomeContext {
+struct HomeContext<'a> {
+    site: Site<'a>,
     blogs: Vec<WebSimpleBlog>,
+    pagination: Pagination,
+}
+
+// Offset of the first blog of a page. Pages too far to be counted
+// cannot have any blog.
+pub(super) fn page_offset(page: i64, page_size: i64) -> Result<i64> {
+    (page - 1).checked_mul(page_size).ok_or(WebError::NotFound)
+}
+
+#[derive(Serialize)]
+pub(super) struct Pagination {
+    page: i64,
+    total_pages: i64,
+    prev_url: Option<String>,
+    next_url: Option<String>,
+}
+
+impl Pagination {
+    // first_url is the canonical url of page 1, page_prefix is
+    // followed by the page number for every other page.
+    pub(super) fn new(
+        page: i64,
+        total: i64,
+        page_size: i64,
+        first_url: &str,
+        page_prefix: &str,
+    ) -> Self {
+        let total_pages = ((total + page_size - 1) / page_size).max(1);
+
+        let page_url = |n: i64| {
+            if n == 1 {
+                first_url.to_string()
+            } else {
+                format!("{}{}", page_prefix, n)
+            }
+        };
+
+        Pagination {
+            page,
+            total_pages,
+            prev_url: (page > 1).then(|| page_url(page - 1)),
+            next_url: (page < total_pages).then(|| page_url(page + 1)),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -40,7 +91,25 @@ struct WebSimpleBlog {
     preview: String,
     create_time: String,
     edit_time: String,
-    tags: Vec<String>,
+    tags: Vec<TagLink>,
+}
+
+// A tag as linked from a blog.
+#[derive(Serialize)]
+pub(super) struct TagLink {
+    name: String,
+    slug: String,
+}
+
+pub(super) fn tag_links(names: &[String], slugs: &[String]) -> Vec<TagLink> {
+    names
+        .iter()
+        .zip(slugs)
+        .map(|(name, slug)| TagLink {
+            name: name.clone(),
+            slug: slug.clone(),
+        })
+        .collect()
 }
 
 impl SimpleBlog {
@@ -55,11 +124,18 @@ impl SimpleBlog {
             preview: self.preview.clone(),
             create_time,
             edit_time,
-            tags: self.tags.clone(),
+            tags: tag_links(&self.tags, &self.tag_slugs),
         }
     }
 }
 
+#[derive(Serialize)]
+struct PostContext<'a> {
+    site: Site<'a>,
+    #[serde(flatten)]
+    blog: WebBlog,
+}
+
 #[derive(Serialize)]
 struct WebBlog {
     user_id: i64,
@@ -69,28 +145,47 @@ struct WebBlog {
     content: String,
     create_time: String,
     edit_time: String,
-    tags: Vec<String>,
+    tags: Vec<TagLink>,
+    toc: Option<Toc>,
+}
+
+// Headings of a post, nested by level.
+#[derive(Serialize)]
+struct Toc {
+    entries: Vec<TocEntry>,
 }
 
 impl FullBlog {
-    fn to_web_blog(&self) -> WebBlog {
+    fn to_web_blog(&self, toc_min_length: usize) -> WebBlog {
         let create_time = format_datetime(self.create_time);
         let edit_time = format_datetime(self.edit_time);
 
+        let show_toc = match self.toc {
+            TocMode::Auto => self.content.chars().count() >= toc_min_length,
+            TocMode::Show => true,
+            TocMode::Hide => false,
+        };
+        // A post without headings has nothing to show.
+        let toc = show_toc
+            .then(|| toc::table_of_contents(&self.rendered_content))
+            .filter(|entries| !entries.is_empty())
+            .map(|entries| Toc { entries });
+
         WebBlog {
             user_id: self.user_id,
             url: self.url.clone(),
             title: self.title.clone(),
             preview: self.preview.clone(),
-            content: self.content.clone(),
+            content: self.rendered_content.clone(),
             create_time,
             edit_time,
-            tags: self.tags.clone(),
+            tags: tag_links(&self.tags, &self.tag_slugs),
+            toc,
         }
     }
 }
 
-fn format_datetime(dt: DateTime<Utc>) -> String {
+pub(super) fn format_datetime(dt: DateTime<Utc>) -> String {
     dt.format("%Y-%m-%d").to_string()
 }
 
@@ -119,32 +214,63 @@ fn simple_blogs_to_web_simple_blogs(blogs: Vec<SimpleBlog>) -> Vec<WebSimpleBlog
 }
 
 async fn show_home_handler(State(state): State<AppState>) -> Result {
-    let home_str = include_str!("templates/home.html");
+    render_home_page(state, 1).await
+}
+
+async fn show_home_page_handler(State(state): State<AppState>, Path(n): Path<i64>) -> Result {
+    render_home_page(state, n).await
+}
+
+async fn render_home_page(state: AppState, page: i64) -> Result {
+    if page < 1 {
+        return Err(WebError::NotFound);
+    }
+
+    let page_size = state.config.page_size.max(1);
+    let offset = page_offset(page, page_size)?;
 
     let mut conn = get_conn_from_pool(state.db).await?;
 
-    let blogs = blogs::get_all_simple_blogs(&mut conn)
+    let blogs = blogs::get_all_simple_blogs(Some(page_size), offset, &mut conn)
+        .await
+        .map_err(WebError::SqlxError)?;
+
+    if blogs.is_empty() && page > 1 {
+        return Err(WebError::NotFound);
+    }
+
+    let total = blogs::count_simple_blogs(&BlogFilter::default(), &mut conn)
         .await
         .map_err(WebError::SqlxError)?;
 
+    let site = Site::new(&state.config);
+
+    let pagination = Pagination::new(
+        page,
+        total,
+        page_size,
+        &format!("{}/", site.base_url),
+        &format!("{}/page/", site.base_url),
+    );
+
     let context = HomeContext {
+        site,
         blogs: simple_blogs_to_web_simple_blogs(blogs),
+        pagination,
     };
 
-    let mut tt = TinyTemplate::new();
-    tt.add_template("home", home_str)
-        .map_err(WebError::TemplateError)?;
-
-    let rendered = tt
-        .render("home", &context)
+    let rendered = state
+        .theme
+        .render("home.html", &context)
         .map_err(WebError::TemplateError)?;
 
     Ok(Html(rendered))
 }
 
-async fn show_blog_handler(State(state): State<AppState>, Path(url): Path<String>) -> Result {
-    let post_str = include_str!("templates/post.html");
-
+async fn show_blog_handler(
+    State(state): State<AppState>,
+    Path(url): Path<String>,
+) -> Result<Response> {
     if url.is_empty() {
         return Err(WebError::NotFound);
     }
@@ -153,80 +279,226 @@ async fn show_blog_handler(State(state): State<AppState>, Path(url): Path<String
 
     let mut conn = get_conn_from_pool(state.db).await?;
 
-    let blog = blogs::get_full_blog_by_url(url, &mut conn)
-        .await
-        .map_err(WebError::SqlxError)?;
-
-    let web_blog = blog.to_web_blog();
+    let blog = match blogs::get_full_blog_by_url(url.clone(), &mut conn).await {
+        Ok(blog) => blog,
+        Err(sqlx::Error::RowNotFound) => {
+            // The url may have been changed, send the reader to the
+            // current one.
+            let current_url = blogs::get_current_url_by_old_url(url, &mut conn)
+                .await
+                .map_err(WebError::SqlxError)?;
+
+            return Ok(moved_permanently(format!(
+                "{}/posts/{}.html",
+                state.config.base_url, current_url
+            )));
+        }
+        Err(err) => return Err(WebError::SqlxError(err)),
+    };
 
-    let mut tt = TinyTemplate::new();
-    tt.set_default_formatter(&tinytemplate::format_unescaped);
-    tt.add_template("post", post_str)
-        .map_err(WebError::TemplateError)?;
+    let context = PostContext {
+        site: Site::new(&state.config),
+        blog: blog.to_web_blog(state.config.toc_min_length),
+    };
 
-    let rendered = tt
-        .render("post", &web_blog)
+    let rendered = state
+        .theme
+        .render("post.html", &context)
         .map_err(WebError::TemplateError)?;
 
-    Ok(Html(rendered))
+    Ok(Html(rendered).into_response())
 }
 
 #[derive(Serialize)]
-struct TagContext {
+struct TagContext<'a> {
+    site: Site<'a>,
     blogs: Vec<WebSimpleBlog>,
     tag: String,
+    slug: String,
+    description: Option<String>,
+    pagination: Pagination,
+}
+
+async fn show_tag_handler(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Response> {
+    let slug = slug.strip_suffix(".html").unwrap_or(&slug).to_string();
+    render_tag_page(state, slug, 1).await
 }
 
-async fn show_tag_handler(State(state): State<AppState>, Path(name): Path<String>) -> Result {
-    let name = remove_html_extension(name);
-    let tag_str = include_str!("templates/tag.html");
+async fn show_tag_page_handler(
+    State(state): State<AppState>,
+    Path((slug, n)): Path<(String, i64)>,
+) -> Result<Response> {
+    render_tag_page(state, slug, n).await
+}
+
+async fn render_tag_page(state: AppState, slug: String, page: i64) -> Result<Response> {
+    if page < 1 {
+        return Err(WebError::NotFound);
+    }
+
+    let page_size = state.config.page_size.max(1);
 
     let mut conn = get_conn_from_pool(state.db).await?;
 
-    let blogs = tags::get_simple_blogs_by_tag_name(name.clone(), &mut conn)
+    let tag = match tags::get_tag_by_slug(&slug, &mut conn)
+        .await
+        .map_err(WebError::SqlxError)?
+    {
+        Some(tag) => tag,
+        None => {
+            // Tag pages used to be reached by name, and renamed tags
+            // keep their old slugs, send the reader to the slug.
+            let old_tag = match tags::get_tag_by_old_slug(&slug, &mut conn)
+                .await
+                .map_err(WebError::SqlxError)?
+            {
+                Some(tag) => Some(tag),
+                None => tags::get_tag_by_name(&slug, &mut conn)
+                    .await
+                    .map_err(WebError::SqlxError)?,
+            };
+            let tag = old_tag.ok_or(WebError::NotFound)?;
+
+            let base_url = &state.config.base_url;
+            return Ok(moved_permanently(match page {
+                1 => format!("{}/tags/{}.html", base_url, tag.slug),
+                _ => format!("{}/tags/{}/page/{}", base_url, tag.slug, page),
+            }));
+        }
+    };
+
+    let blogs = tags::get_simple_blogs_by_tag_name(
+        tag.name.clone(),
+        page_size,
+        page_offset(page, page_size)?,
+        &mut conn,
+    )
+    .await
+    .map_err(WebError::SqlxError)?;
+
+    if blogs.is_empty() && page > 1 {
+        return Err(WebError::NotFound);
+    }
+
+    let filter = BlogFilter {
+        tags: vec![tag.name.clone()],
+        ..Default::default()
+    };
+    let total = blogs::count_simple_blogs(&filter, &mut conn)
         .await
         .map_err(WebError::SqlxError)?;
 
     let web_simple_blogs = simple_blogs_to_web_simple_blogs(blogs);
 
+    let site = Site::new(&state.config);
+
+    let pagination = Pagination::new(
+        page,
+        total,
+        page_size,
+        &format!("{}/tags/{}.html", site.base_url, tag.slug),
+        &format!("{}/tags/{}/page/", site.base_url, tag.slug),
+    );
+
     let context = TagContext {
+        site,
         blogs: web_simple_blogs,
-        tag: name,
+        tag: tag.name,
+        slug: tag.slug,
+        description: tag.description,
+        pagination,
     };
 
-    let mut tt = TinyTemplate::new();
-    tt.add_template("tag", tag_str)
+    let rendered = state
+        .theme
+        .render("tag.html", &context)
         .map_err(WebError::TemplateError)?;
 
-    let rendered = tt
-        .render("tag", &context)
-        .map_err(WebError::TemplateError)?;
+    Ok(Html(rendered).into_response())
+}
 
-    Ok(Html(rendered))
+#[derive(Deserialize)]
+struct ListTagsQuery {
+    // The tags are shown as a cloud unless a sort is asked for.
+    sort: Option<TagOrder>,
 }
 
 #[derive(Serialize)]
-struct ListTagsContext {
-    tags: Vec<String>,
+struct WebTag {
+    name: String,
+    slug: String,
+    blog_count: i64,
+    latest_blog_time: String,
+    // From 1 to CLOUD_WEIGHTS, the more blogs the heavier.
+    weight: i64,
 }
 
-async fn list_tags_handler(State(state): State<AppState>) -> Result {
-    let list_tags_str = include_str!("templates/list_tags.html");
+#[derive(Serialize)]
+struct ListTagsContext<'a> {
+    site: Site<'a>,
+    tags: Vec<WebTag>,
+    cloud: bool,
+    sort: &'static str,
+}
+
+const CLOUD_WEIGHTS: i64 = 5;
 
+// Tags are weighted on a log scale, so that a few very common tags do
+// not make every other tag look the same.
+fn tag_weight(blog_count: i64, max_blog_count: i64) -> i64 {
+    if max_blog_count <= 1 {
+        return 1;
+    }
+
+    let ratio = (blog_count as f64).ln() / (max_blog_count as f64).ln();
+    1 + (ratio * (CLOUD_WEIGHTS - 1) as f64).round() as i64
+}
+
+async fn list_tags_handler(
+    State(state): State<AppState>,
+    Query(query): Query<ListTagsQuery>,
+) -> Result {
     let mut conn = get_conn_from_pool(state.db).await?;
 
-    let tags = get_all_tag_names(&mut conn)
+    let order = query.sort.unwrap_or_default();
+    let summaries = tags::get_tag_summaries(&BlogFilter::default(), order, &mut conn)
         .await
         .map_err(WebError::SqlxError)?;
 
-    let context = ListTagsContext { tags };
-
-    let mut tt = TinyTemplate::new();
-    tt.add_template("list_tags", list_tags_str)
-        .map_err(WebError::TemplateError)?;
+    let max_blog_count = summaries
+        .iter()
+        .map(|summary| summary.blog_count)
+        .max()
+        .unwrap_or(0);
+
+    let tags = summaries
+        .into_iter()
+        .map(|summary| WebTag {
+            weight: tag_weight(summary.blog_count, max_blog_count),
+            latest_blog_time: format_datetime(summary.latest_blog_time),
+            blog_count: summary.blog_count,
+            name: summary.name,
+            slug: summary.slug,
+        })
+        .collect();
+
+    let context = ListTagsContext {
+        site: Site::new(&state.config),
+        tags,
+        cloud: query.sort.is_none(),
+        sort: match order {
+            TagOrder::Name => "name",
+            TagOrder::Count => "count",
+            TagOrder::Recent => "recent",
+        },
+    };
 
-    let rendered = tt
-        .render("list_tags", &context)
+    let rendered = state
+        .theme
+        .render("list_tags.html", &context)
         .map_err(WebError::TemplateError)?;
 
     Ok(Html(rendered))
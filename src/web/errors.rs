@@ -1,45 +1,100 @@
 use axum::{
-    http::StatusCode,
-    response::{Html, IntoResponse},
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+    Extension,
 };
+use serde::Serialize;
 
 use tracing::error;
 
+use crate::app::AppState;
+
+use super::Site;
+
 pub enum WebError {
     SqlxError(sqlx::Error),
     TemplateError(tinytemplate::error::Error),
     NotFound,
 }
 
-fn server_error_response() -> (StatusCode, Html<String>) {
-    let server_error_str = include_str!("templates/server_error.html");
+// The error pages need the site configuration, which a WebError does
+// not have, so the response only carries the kind of page and
+// render_error_page fills in the body.
+#[derive(Clone, Copy)]
+enum ErrorPage {
+    NotFound,
+    ServerError,
+}
+
+impl ErrorPage {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        match self {
+            Self::NotFound => "Not Found",
+            Self::ServerError => "Internal Server Error",
+        }
+    }
+
+    fn into_response(self) -> Response {
+        (self.status(), Extension(self)).into_response()
+    }
+}
 
-    (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Html(server_error_str.to_string()),
-    )
+#[derive(Serialize)]
+struct ErrorContext<'a> {
+    site: Site<'a>,
+    error: &'static str,
 }
 
-fn not_found_response() -> (StatusCode, Html<String>) {
-    let not_found_str = include_str!("templates/not_found.html");
-    (StatusCode::NOT_FOUND, Html(not_found_str.to_string()))
+pub async fn render_error_page<B>(
+    State(state): State<AppState>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let response = next.run(req).await;
+
+    let page = match response.extensions().get::<ErrorPage>() {
+        Some(page) => *page,
+        None => return response,
+    };
+
+    let context = ErrorContext {
+        site: Site::new(&state.config),
+        error: page.message(),
+    };
+
+    match state.theme.render("error.html", &context) {
+        Ok(rendered) => (page.status(), Html(rendered)).into_response(),
+        Err(err) => {
+            error!("{:?}", err);
+            page.status().into_response()
+        }
+    }
 }
 
 impl IntoResponse for WebError {
     fn into_response(self) -> axum::response::Response {
         match self {
             Self::SqlxError(err) => match err {
-                sqlx::Error::RowNotFound => not_found_response().into_response(),
+                sqlx::Error::RowNotFound => ErrorPage::NotFound.into_response(),
                 _ => {
                     error!("{:?}", err);
-                    server_error_response().into_response()
+                    ErrorPage::ServerError.into_response()
                 }
             },
             Self::TemplateError(err) => {
                 error!("{:?}", err);
-                server_error_response().into_response()
+                ErrorPage::ServerError.into_response()
             }
-            Self::NotFound => not_found_response().into_response(),
+            Self::NotFound => ErrorPage::NotFound.into_response(),
         }
     }
 }
@@ -0,0 +1,407 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+
+use crate::{
+    app::AppState,
+    config::Config,
+    data::{
+        blogs::{self, FullBlog},
+        tags, users,
+    },
+};
+
+use super::{
+    blogs::page_offset,
+    errors::WebError,
+    helpers::{get_conn_from_pool, moved_permanently},
+    theme::Theme,
+    Result,
+};
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/feed.atom", get(show_atom_feed_handler))
+        .route("/feed.rss", get(show_rss_feed_handler))
+        .route("/feed.json", get(show_json_feed_handler))
+        .route("/tags/:slug/feed.atom", get(show_tag_atom_feed_handler))
+        .route("/tags/:slug/feed.json", get(show_tag_json_feed_handler))
+        .with_state(state)
+}
+
+#[derive(Clone, Copy)]
+enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl FeedFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            Self::Atom => "application/atom+xml; charset=utf-8",
+            Self::Rss => "application/rss+xml; charset=utf-8",
+        }
+    }
+
+    fn format_datetime(&self, dt: DateTime<Utc>) -> String {
+        match self {
+            Self::Atom => dt.to_rfc3339_opts(SecondsFormat::Secs, true),
+            Self::Rss => dt.to_rfc2822(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FeedContext {
+    title: String,
+    author: String,
+    site_url: String,
+    self_url: String,
+    updated: String,
+    entries: Vec<FeedEntry>,
+}
+
+#[derive(Serialize)]
+struct FeedEntry {
+    id: String,
+    title: String,
+    url: String,
+    published: String,
+    updated: String,
+    tags: Vec<String>,
+    summary: String,
+    content: Option<String>,
+}
+
+// The id of an entry must never change, so it is built from the blog
+// id and creation date instead of the url, which can be changed.
+fn entry_id(base_url: &str, blog: &FullBlog) -> String {
+    let host = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest)
+        .split(['/', ':'])
+        .next()
+        .unwrap_or_default();
+
+    format!(
+        "tag:{},{}:/posts/{}",
+        host,
+        blog.create_time.format("%Y-%m-%d"),
+        blog.id
+    )
+}
+
+fn blogs_to_feed_context(
+    blogs: Vec<FullBlog>,
+    format: FeedFormat,
+    config: &Config,
+    title: String,
+    site_url: String,
+    self_url: String,
+) -> FeedContext {
+    let updated = blogs
+        .iter()
+        .map(|blog| blog.edit_time)
+        .max()
+        .unwrap_or_else(Utc::now);
+
+    let entries = blogs
+        .into_iter()
+        .map(|blog| FeedEntry {
+            id: entry_id(&config.base_url, &blog),
+            url: format!("{}/posts/{}.html", config.base_url, blog.url),
+            published: format.format_datetime(blog.create_time),
+            updated: format.format_datetime(blog.edit_time),
+            content: (!config.feed_preview_only).then_some(blog.rendered_content),
+            title: blog.title,
+            tags: blog.tags,
+            summary: blog.preview,
+        })
+        .collect();
+
+    FeedContext {
+        title,
+        author: config.site_author.clone(),
+        site_url,
+        self_url,
+        updated: format.format_datetime(updated),
+        entries,
+    }
+}
+
+fn render_feed(theme: &Theme, context: &FeedContext, format: FeedFormat) -> Result<Response> {
+    let name = match format {
+        FeedFormat::Atom => "feed.atom",
+        FeedFormat::Rss => "feed.rss",
+    };
+
+    let rendered = theme
+        .render(name, context)
+        .map_err(WebError::TemplateError)?;
+
+    Ok(([(header::CONTENT_TYPE, format.content_type())], rendered).into_response())
+}
+
+async fn render_site_feed(state: AppState, format: FeedFormat, path: &str) -> Result<Response> {
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let blogs = blogs::get_all_full_blogs(Some(state.config.feed_size), 0, &mut conn)
+        .await
+        .map_err(WebError::SqlxError)?;
+
+    let config = &state.config;
+
+    let context = blogs_to_feed_context(
+        blogs,
+        format,
+        config,
+        config.site_title.clone(),
+        format!("{}/", config.base_url),
+        format!("{}{}", config.base_url, path),
+    );
+
+    render_feed(&state.theme, &context, format)
+}
+
+async fn show_atom_feed_handler(State(state): State<AppState>) -> Result<Response> {
+    render_site_feed(state, FeedFormat::Atom, "/feed.atom").await
+}
+
+async fn show_rss_feed_handler(State(state): State<AppState>) -> Result<Response> {
+    render_site_feed(state, FeedFormat::Rss, "/feed.rss").await
+}
+
+async fn show_tag_atom_feed_handler(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Response> {
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let tag = match get_tag(&slug, &mut conn).await? {
+        Some(tag) => tag,
+        None => return moved_tag_feed(&slug, "feed.atom", &state.config, &mut conn).await,
+    };
+
+    let blogs = tags::get_full_blogs_by_tag_name(
+        tag.name.clone(),
+        Some(state.config.feed_size),
+        0,
+        &mut conn,
+    )
+    .await
+    .map_err(WebError::SqlxError)?;
+
+    let config = &state.config;
+
+    let context = blogs_to_feed_context(
+        blogs,
+        FeedFormat::Atom,
+        config,
+        format!("{} - {}", tag.name, config.site_title),
+        format!("{}/tags/{}.html", config.base_url, tag.slug),
+        format!("{}/tags/{}/feed.atom", config.base_url, tag.slug),
+    );
+
+    render_feed(&state.theme, &context, FeedFormat::Atom)
+}
+
+#[derive(Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_url: Option<String>,
+    language: &'static str,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize, Clone)]
+struct JsonFeedAuthor {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    // Only one of them is given, the preview is plain text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_text: Option<String>,
+    summary: String,
+    date_published: String,
+    date_modified: String,
+    tags: Vec<String>,
+    authors: Vec<JsonFeedAuthor>,
+}
+
+#[derive(Deserialize)]
+struct JsonFeedQuery {
+    page: Option<i64>,
+}
+
+// The pages of a JSON feed follow the pages of the html site (same
+// page size), each page links to the next one through next_url.
+async fn render_json_feed(
+    state: AppState,
+    tag_slug: Option<String>,
+    page: i64,
+) -> Result<Response> {
+    if page < 1 {
+        return Err(WebError::NotFound);
+    }
+
+    let page_size = state.config.page_size.max(1);
+    let offset = page_offset(page, page_size)?;
+
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let tag = match tag_slug {
+        Some(slug) => match get_tag(&slug, &mut conn).await? {
+            Some(tag) => Some(tag),
+            None => {
+                let feed = match page {
+                    1 => "feed.json".to_string(),
+                    _ => format!("feed.json?page={}", page),
+                };
+                return moved_tag_feed(&slug, &feed, &state.config, &mut conn).await;
+            }
+        },
+        None => None,
+    };
+
+    // One more than needed, to know whether there is a next page.
+    let mut blogs = match &tag {
+        Some(tag) => {
+            tags::get_full_blogs_by_tag_name(
+                tag.name.clone(),
+                Some(page_size + 1),
+                offset,
+                &mut conn,
+            )
+            .await
+        }
+        None => blogs::get_all_full_blogs(Some(page_size + 1), offset, &mut conn).await,
+    }
+    .map_err(WebError::SqlxError)?;
+
+    if blogs.is_empty() && page > 1 {
+        return Err(WebError::NotFound);
+    }
+
+    let has_next = blogs.len() as i64 > page_size;
+    blogs.truncate(page_size as usize);
+
+    let mut authors: HashMap<i64, JsonFeedAuthor> = HashMap::new();
+    for blog in &blogs {
+        if let Entry::Vacant(entry) = authors.entry(blog.user_id) {
+            let user = users::get_user(blog.user_id, &mut conn)
+                .await
+                .map_err(WebError::SqlxError)?;
+            entry.insert(JsonFeedAuthor {
+                name: user.username,
+            });
+        }
+    }
+
+    let config = &state.config;
+
+    let (title, home_page_url, feed_url) = match &tag {
+        Some(tag) => (
+            format!("{} - {}", tag.name, config.site_title),
+            format!("{}/tags/{}.html", config.base_url, tag.slug),
+            format!("{}/tags/{}/feed.json", config.base_url, tag.slug),
+        ),
+        None => (
+            config.site_title.clone(),
+            format!("{}/", config.base_url),
+            format!("{}/feed.json", config.base_url),
+        ),
+    };
+
+    let preview_only = config.feed_preview_only;
+
+    let items = blogs
+        .into_iter()
+        .map(|blog| JsonFeedItem {
+            id: entry_id(&config.base_url, &blog),
+            url: format!("{}/posts/{}.html", config.base_url, blog.url),
+            authors: authors.get(&blog.user_id).cloned().into_iter().collect(),
+            date_published: FeedFormat::Atom.format_datetime(blog.create_time),
+            date_modified: FeedFormat::Atom.format_datetime(blog.edit_time),
+            content_html: (!preview_only).then_some(blog.rendered_content),
+            content_text: preview_only.then(|| blog.preview.clone()),
+            title: blog.title,
+            summary: blog.preview,
+            tags: blog.tags,
+        })
+        .collect();
+
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title,
+        home_page_url,
+        next_url: has_next.then(|| format!("{}?page={}", feed_url, page + 1)),
+        feed_url,
+        language: "en",
+        items,
+    };
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/feed+json; charset=utf-8")],
+        Json(feed),
+    )
+        .into_response())
+}
+
+async fn show_json_feed_handler(
+    State(state): State<AppState>,
+    Query(query): Query<JsonFeedQuery>,
+) -> Result<Response> {
+    render_json_feed(state, None, query.page.unwrap_or(1)).await
+}
+
+async fn show_tag_json_feed_handler(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+    Query(query): Query<JsonFeedQuery>,
+) -> Result<Response> {
+    render_json_feed(state, Some(slug), query.page.unwrap_or(1)).await
+}
+
+async fn get_tag(slug: &str, conn: &mut PgConnection) -> Result<Option<tags::Tag>> {
+    tags::get_tag_by_slug(slug, conn)
+        .await
+        .map_err(WebError::SqlxError)
+}
+
+// The feeds of a renamed tag are still found at its old slugs.
+async fn moved_tag_feed(
+    slug: &str,
+    feed: &str,
+    config: &Config,
+    conn: &mut PgConnection,
+) -> Result<Response> {
+    let tag = tags::get_tag_by_old_slug(slug, conn)
+        .await
+        .map_err(WebError::SqlxError)?
+        .ok_or(WebError::NotFound)?;
+
+    Ok(moved_permanently(format!(
+        "{}/tags/{}/{}",
+        config.base_url, tag.slug, feed
+    )))
+}
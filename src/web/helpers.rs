@@ -1,3 +1,5 @@
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
 use sqlx::pool::PoolConnection;
 use sqlx::{PgPool, Postgres};
 
@@ -8,3 +10,12 @@ pub async fn get_conn_from_pool(pool: PgPool) -> Result<PoolConnection<Postgres>
     let conn = pool.acquire().await.map_err(WebError::SqlxError)?;
     Ok(conn)
 }
+
+// axum's Redirect::permanent answers 308, old links should get a 301.
+pub fn moved_permanently(location: String) -> Response {
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, location)],
+    )
+        .into_response()
+}
@@ -0,0 +1,37 @@
+use std::sync::OnceLock;
+
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use crate::{app::AppState, render::highlight};
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/highlight.css", get(show_highlight_stylesheet_handler))
+        .with_state(state)
+}
+
+// The theme cannot change while running, so the stylesheet is only
+// generated once.
+async fn show_highlight_stylesheet_handler(State(state): State<AppState>) -> Response {
+    static STYLESHEET: OnceLock<String> = OnceLock::new();
+
+    let stylesheet = STYLESHEET.get_or_init(|| {
+        // The theme is checked when the config is parsed.
+        highlight::stylesheet(&state.config.highlight_theme).unwrap_or_default()
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/css; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        stylesheet.as_str(),
+    )
+        .into_response()
+}
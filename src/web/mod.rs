@@ -1,15 +1,59 @@
-use axum::{response::Html, Router};
+use axum::{middleware, response::Html, Router};
+use serde::Serialize;
 
-use crate::app::AppState;
+use crate::{
+    app::AppState,
+    config::{Config, NavLink},
+};
 
 mod blogs;
 mod errors;
+mod feeds;
 mod helpers;
+mod highlight;
+mod search;
+mod sitemap;
+pub mod theme;
 
 use errors::WebError;
 
 pub type Result<T = Html<String>, E = WebError> = std::result::Result<T, E>;
 
+// Identity of the site, passed into every template as `site`.
+#[derive(Serialize)]
+struct Site<'a> {
+    base_url: &'a str,
+    title: &'a str,
+    asset_url: &'a str,
+    stylesheet_url: &'a str,
+    nav_links: &'a [NavLink],
+    nav_links_right: &'a [NavLink],
+    footer: Option<&'a str>,
+}
+
+impl<'a> Site<'a> {
+    fn new(config: &'a Config) -> Self {
+        Site {
+            base_url: &config.base_url,
+            title: &config.site_title,
+            asset_url: &config.asset_url,
+            stylesheet_url: &config.stylesheet_url,
+            nav_links: &config.nav_links,
+            nav_links_right: &config.nav_links_right,
+            footer: config.footer.as_deref(),
+        }
+    }
+}
+
 pub fn routes(state: AppState) -> Router {
-    Router::new().merge(blogs::routes(state))
+    Router::new()
+        .merge(blogs::routes(state.clone()))
+        .merge(feeds::routes(state.clone()))
+        .merge(sitemap::routes(state.clone()))
+        .merge(highlight::routes(state.clone()))
+        .merge(search::routes(state.clone()))
+        .layer(middleware::from_fn_with_state(
+            state,
+            errors::render_error_page,
+        ))
 }
@@ -0,0 +1,146 @@
+use axum::{
+    extract::{Query, State},
+    response::Html,
+    routing::get,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::AppState,
+    data::blogs::{self, BlogFilter, SearchResult},
+};
+
+use super::{
+    blogs::{format_datetime, page_offset, tag_links, Pagination, TagLink},
+    errors::WebError,
+    helpers::get_conn_from_pool,
+    Result, Site,
+};
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/search", get(show_search_handler))
+        .with_state(state)
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: Option<String>,
+    tag: Option<String>,
+    page: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct WebSearchResult {
+    url: String,
+    title: String,
+    snippet: String,
+    create_time: String,
+    edit_time: String,
+    tags: Vec<TagLink>,
+}
+
+impl SearchResult {
+    fn to_web_search_result(&self) -> WebSearchResult {
+        WebSearchResult {
+            url: self.url.clone(),
+            title: self.title.clone(),
+            snippet: self.snippet.clone(),
+            create_time: format_datetime(self.create_time),
+            edit_time: format_datetime(self.edit_time),
+            tags: tag_links(&self.tags, &self.tag_slugs),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct SearchContext<'a> {
+    site: Site<'a>,
+    // Empty until something is searched for.
+    query: String,
+    tag: Option<String>,
+    results: Vec<WebSearchResult>,
+    total: i64,
+    pagination: Option<Pagination>,
+}
+
+async fn show_search_handler(
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+) -> Result {
+    let search = query.q.unwrap_or_default().trim().to_string();
+    let tag = query.tag.filter(|tag| !tag.is_empty());
+    let page = query.page.unwrap_or(1);
+    if page < 1 {
+        return Err(WebError::NotFound);
+    }
+
+    let site = Site::new(&state.config);
+
+    let mut context = SearchContext {
+        site,
+        query: search.clone(),
+        tag: tag.clone(),
+        results: Vec::new(),
+        total: 0,
+        pagination: None,
+    };
+
+    if !search.is_empty() {
+        let page_size = state.config.page_size.max(1);
+
+        let filter = BlogFilter {
+            tags: tag.iter().cloned().collect(),
+            ..Default::default()
+        };
+
+        let mut conn = get_conn_from_pool(state.db.clone()).await?;
+
+        let results = blogs::search_blogs(
+            &search,
+            &filter,
+            page_size,
+            page_offset(page, page_size)?,
+            &mut conn,
+        )
+        .await
+        .map_err(WebError::SqlxError)?;
+
+        if results.is_empty() && page > 1 {
+            return Err(WebError::NotFound);
+        }
+
+        let total = blogs::count_search_results(&search, &filter, &mut conn)
+            .await
+            .map_err(WebError::SqlxError)?;
+
+        let mut params = vec![("q", search.as_str())];
+        if let Some(tag) = &tag {
+            params.push(("tag", tag));
+        }
+        // Only made of strings, it cannot fail.
+        let params = serde_urlencoded::to_string(&params).unwrap_or_default();
+        let first_url = format!("{}/search?{}", context.site.base_url, params);
+
+        context.results = results
+            .iter()
+            .map(SearchResult::to_web_search_result)
+            .collect();
+        context.total = total;
+        context.pagination = Some(Pagination::new(
+            page,
+            total,
+            page_size,
+            &first_url,
+            &format!("{}&page=", first_url),
+        ));
+    }
+
+    let rendered = state
+        .theme
+        .render("search.html", &context)
+        .map_err(WebError::TemplateError)?;
+
+    Ok(Html(rendered))
+}
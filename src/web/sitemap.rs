@@ -0,0 +1,170 @@
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::{
+    app::AppState,
+    data::{blogs, tags},
+};
+
+use super::{errors::WebError, helpers::get_conn_from_pool, theme::Theme, Result};
+
+// A single sitemap file may not list more than 50,000 urls.
+const MAX_URLS_PER_SITEMAP: usize = 50_000;
+
+const XML_CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+
+pub fn routes(state: AppState) -> Router {
+    Router::new()
+        .route("/sitemap.xml", get(show_sitemap_handler))
+        .route("/sitemaps/:file", get(show_sitemap_part_handler))
+        .route("/robots.txt", get(show_robots_handler))
+        .with_state(state)
+}
+
+#[derive(Serialize)]
+struct SitemapUrl {
+    loc: String,
+    lastmod: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SitemapContext<'a> {
+    urls: &'a [SitemapUrl],
+}
+
+#[derive(Serialize)]
+struct SitemapIndexContext {
+    sitemaps: Vec<SitemapUrl>,
+}
+
+fn format_datetime(dt: DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Every public url of the site, the home page first, then the posts
+// and the tag pages.
+async fn get_all_urls(state: AppState) -> Result<Vec<SitemapUrl>> {
+    let base_url = &state.config.base_url;
+
+    let mut conn = get_conn_from_pool(state.db).await?;
+
+    let blogs = blogs::get_all_simple_blogs(None, 0, &mut conn)
+        .await
+        .map_err(WebError::SqlxError)?;
+
+    let tag_slugs = tags::get_all_tag_slugs(&mut conn)
+        .await
+        .map_err(WebError::SqlxError)?;
+
+    let mut urls: Vec<SitemapUrl> = Vec::with_capacity(1 + blogs.len() + tag_slugs.len());
+
+    urls.push(SitemapUrl {
+        loc: format!("{}/", base_url),
+        lastmod: blogs
+            .iter()
+            .map(|blog| blog.edit_time)
+            .max()
+            .map(format_datetime),
+    });
+
+    for blog in blogs {
+        urls.push(SitemapUrl {
+            loc: format!("{}/posts/{}.html", base_url, blog.url),
+            lastmod: Some(format_datetime(blog.edit_time)),
+        });
+    }
+
+    for slug in tag_slugs {
+        urls.push(SitemapUrl {
+            loc: format!("{}/tags/{}.html", base_url, slug),
+            lastmod: None,
+        });
+    }
+
+    Ok(urls)
+}
+
+fn render_xml<C: Serialize>(theme: &Theme, name: &str, context: &C) -> Result<Response> {
+    let rendered = theme
+        .render(name, context)
+        .map_err(WebError::TemplateError)?;
+
+    Ok(([(header::CONTENT_TYPE, XML_CONTENT_TYPE)], rendered).into_response())
+}
+
+fn render_urlset(theme: &Theme, urls: &[SitemapUrl]) -> Result<Response> {
+    render_xml(theme, "sitemap.xml", &SitemapContext { urls })
+}
+
+// Small sites get a single sitemap, bigger ones get a sitemap index
+// pointing to /sitemaps/1.xml, /sitemaps/2.xml, ...
+async fn show_sitemap_handler(State(state): State<AppState>) -> Result<Response> {
+    let urls = get_all_urls(state.clone()).await?;
+
+    if urls.len() <= MAX_URLS_PER_SITEMAP {
+        return render_urlset(&state.theme, &urls);
+    }
+
+    let sitemaps = urls
+        .chunks(MAX_URLS_PER_SITEMAP)
+        .enumerate()
+        .map(|(i, chunk)| SitemapUrl {
+            loc: format!("{}/sitemaps/{}.xml", state.config.base_url, i + 1),
+            lastmod: chunk.iter().filter_map(|url| url.lastmod.clone()).max(),
+        })
+        .collect();
+
+    render_xml(
+        &state.theme,
+        "sitemap_index.xml",
+        &SitemapIndexContext { sitemaps },
+    )
+}
+
+async fn show_sitemap_part_handler(
+    State(state): State<AppState>,
+    Path(file): Path<String>,
+) -> Result<Response> {
+    let n: usize = file
+        .strip_suffix(".xml")
+        .and_then(|n| n.parse().ok())
+        .filter(|n| *n >= 1)
+        .ok_or(WebError::NotFound)?;
+
+    let urls = get_all_urls(state.clone()).await?;
+
+    match urls.chunks(MAX_URLS_PER_SITEMAP).nth(n - 1) {
+        Some(chunk) => render_urlset(&state.theme, chunk),
+        None => Err(WebError::NotFound),
+    }
+}
+
+async fn show_robots_handler(State(state): State<AppState>) -> Response {
+    let mut robots = String::from("User-agent: *\n");
+
+    if state.config.robots_disallow.is_empty() {
+        robots.push_str("Disallow:\n");
+    }
+
+    for path in &state.config.robots_disallow {
+        robots.push_str(&format!("Disallow: {}\n", path));
+    }
+
+    robots.push_str(&format!(
+        "\nSitemap: {}/sitemap.xml\n",
+        state.config.base_url
+    ));
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        robots,
+    )
+        .into_response()
+}
@@ -0,0 +1,202 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, SystemTime},
+};
+
+use serde::Serialize;
+use tinytemplate::{format_unescaped, TinyTemplate};
+use tracing::{error, info};
+
+// Every template of the site, with the version compiled into the
+// binary. A theme directory may override any of them by file name.
+//
+// head.html, header.html and footer.html are the partials shared by
+// every page, called with the site as their context. toc.html renders
+// a level of the table of contents of a post, calling itself for the
+// levels below.
+const EMBEDDED_TEMPLATES: [(&str, &str); 14] = [
+    ("head.html", include_str!("templates/head.html")),
+    ("header.html", include_str!("templates/header.html")),
+    ("footer.html", include_str!("templates/footer.html")),
+    ("home.html", include_str!("templates/home.html")),
+    ("post.html", include_str!("templates/post.html")),
+    ("toc.html", include_str!("templates/toc.html")),
+    ("tag.html", include_str!("templates/tag.html")),
+    ("list_tags.html", include_str!("templates/list_tags.html")),
+    ("search.html", include_str!("templates/search.html")),
+    ("error.html", include_str!("templates/error.html")),
+    ("feed.atom", include_str!("templates/feed.atom")),
+    ("feed.rss", include_str!("templates/feed.rss")),
+    ("sitemap.xml", include_str!("templates/sitemap.xml")),
+    (
+        "sitemap_index.xml",
+        include_str!("templates/sitemap_index.xml"),
+    ),
+];
+
+// Each load of a theme gets a new generation, so that the compiled
+// copies cached by every thread know when they are stale.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static COMPILED: RefCell<Option<(u64, TinyTemplate<'static>)>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(PathBuf, std::io::Error),
+    Template(&'static str, tinytemplate::error::Error),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            Self::Template(name, err) => write!(f, "invalid template {}: {}", name, err),
+        }
+    }
+}
+
+struct Templates {
+    generation: u64,
+    sources: Vec<(&'static str, &'static str)>,
+}
+
+impl Templates {
+    fn load(dir: Option<&Path>) -> Result<Self, ThemeError> {
+        let mut sources = Vec::with_capacity(EMBEDDED_TEMPLATES.len());
+
+        for (name, embedded) in EMBEDDED_TEMPLATES {
+            let source = match dir.map(|dir| dir.join(name)).filter(|path| path.is_file()) {
+                // Templates are only read again in dev mode, leaking
+                // the few kilobytes of each reload is fine there.
+                Some(path) => {
+                    let source =
+                        std::fs::read_to_string(&path).map_err(|err| ThemeError::Io(path, err))?;
+                    &*Box::leak(source.into_boxed_str())
+                }
+                None => embedded,
+            };
+
+            sources.push((name, source));
+        }
+
+        let templates = Templates {
+            generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
+            sources,
+        };
+
+        // Report broken templates now instead of on the first request.
+        templates.compile()?;
+
+        Ok(templates)
+    }
+
+    fn compile(&self) -> Result<TinyTemplate<'static>, ThemeError> {
+        let mut tt = TinyTemplate::new();
+        tt.add_formatter("unescaped", format_unescaped);
+
+        for (name, source) in &self.sources {
+            tt.add_template(name, source)
+                .map_err(|err| ThemeError::Template(name, err))?;
+        }
+
+        Ok(tt)
+    }
+}
+
+// The templates of the site, loaded once and shared by every handler.
+//
+// TinyTemplate cannot be shared between threads, so the theme keeps
+// the template sources and each thread compiles its own copy the
+// first time it renders a given generation.
+pub struct Theme {
+    dir: Option<PathBuf>,
+    templates: RwLock<Arc<Templates>>,
+}
+
+impl Theme {
+    pub fn load(dir: Option<PathBuf>) -> Result<Self, ThemeError> {
+        let templates = Templates::load(dir.as_deref())?;
+
+        Ok(Theme {
+            dir,
+            templates: RwLock::new(Arc::new(templates)),
+        })
+    }
+
+    pub fn reload(&self) -> Result<(), ThemeError> {
+        let templates = Templates::load(self.dir.as_deref())?;
+        *self.templates.write().unwrap() = Arc::new(templates);
+        Ok(())
+    }
+
+    pub fn render<C: Serialize>(
+        &self,
+        name: &str,
+        context: &C,
+    ) -> Result<String, tinytemplate::error::Error> {
+        let templates = self.templates.read().unwrap().clone();
+
+        COMPILED.with(|compiled| {
+            let mut compiled = compiled.borrow_mut();
+
+            let stale =
+                !matches!(&*compiled, Some((generation, _)) if *generation == templates.generation);
+            if stale {
+                // The sources were checked when they were loaded.
+                let tt = templates.compile().expect("theme templates are valid");
+                *compiled = Some((templates.generation, tt));
+            }
+
+            let (_, tt) = compiled.as_ref().unwrap();
+            tt.render(name, context)
+        })
+    }
+
+    // Modification time of every template in the theme directory,
+    // used to notice added, changed and removed files.
+    fn fingerprint(&self) -> Vec<Option<SystemTime>> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return Vec::new(),
+        };
+
+        EMBEDDED_TEMPLATES
+            .iter()
+            .map(|(name, _)| {
+                std::fs::metadata(dir.join(name))
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+// Reload the theme whenever a template in the theme directory is
+// added, changed or removed. Only meant for development.
+pub async fn watch(theme: Arc<Theme>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    let mut fingerprint = theme.fingerprint();
+
+    loop {
+        interval.tick().await;
+
+        let current = theme.fingerprint();
+        if current == fingerprint {
+            continue;
+        }
+        fingerprint = current;
+
+        match theme.reload() {
+            Ok(()) => info!("theme reloaded"),
+            Err(err) => error!("theme not reloaded: {}", err),
+        }
+    }
+}